@@ -0,0 +1,62 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kibi::{Document, Position};
+
+const LINE_LENGTH: usize = 5_000_000;
+const LINE_COUNT: usize = 1_000_000;
+
+fn single_line_document() -> Document {
+    let mut document = Document::default();
+    document.insert_str(&Position::default(), &"x".repeat(LINE_LENGTH));
+    document
+}
+
+fn many_line_document() -> Document {
+    let mut document = Document::default();
+    let text = vec!["a typical line of a log file"; LINE_COUNT].join("\n");
+    document.insert_str(&Position::default(), &text);
+    document
+}
+
+fn huge_single_line(c: &mut Criterion) {
+    let mut document = single_line_document();
+    let middle = Position { x: LINE_LENGTH / 2, y: 0 };
+    let after = Position { x: middle.x + 1, y: 0 };
+
+    c.bench_function("single line: insert and delete in the middle", |b| {
+        b.iter(|| {
+            document.insert(black_box(&middle), 'y');
+            document.delete_range(&middle, &after);
+        });
+    });
+
+    c.bench_function("single line: insert and delete non-ascii", |b| {
+        b.iter(|| {
+            document.insert(black_box(&middle), 'é');
+            document.delete_range(&middle, &after);
+        });
+    });
+}
+
+fn many_lines(c: &mut Criterion) {
+    let mut document = many_line_document();
+    let middle = Position { x: 4, y: LINE_COUNT / 2 };
+    let next_line = Position { x: 0, y: middle.y + 1 };
+
+    c.bench_function("many lines: split and join a line in the middle", |b| {
+        b.iter(|| {
+            document.new_line(black_box(&middle));
+            document.delete_range(&Position { x: 4, y: middle.y }, &next_line);
+        });
+    });
+
+    c.bench_function("many lines: line lookup", |b| {
+        let mut index = 0;
+        b.iter(|| {
+            index = (index + 7919) % LINE_COUNT;
+            black_box(document.row(index).map(kibi::Row::len));
+        });
+    });
+}
+
+criterion_group!(benches, huge_single_line, many_lines);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+//the file tree shown when browsing a directory
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Entry {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+pub struct Tree {
+    root: PathBuf,
+    //directories whose contents are listed under them
+    expanded: HashSet<PathBuf>,
+}
+
+impl Tree {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            expanded: HashSet::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    //the visible entries in order, directories before files
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        self.list(&self.root, 0, &mut entries);
+        entries
+    }
+
+    fn list(&self, dir: &Path, depth: usize, entries: &mut Vec<Entry>) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut children: Vec<(bool, String)> = read_dir
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                Some((entry.path().is_dir(), name))
+            })
+            .collect();
+        children.sort_by(|(a_dir, a), (b_dir, b)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+        for (is_dir, name) in children {
+            //paths under the current directory are kept short, like `src/main.rs`
+            let path = if dir == Path::new(".") {
+                PathBuf::from(name)
+            } else {
+                dir.join(name)
+            };
+            let expanded = is_dir && self.expanded.contains(&path);
+            entries.push(Entry {
+                path: path.clone(),
+                depth,
+                is_dir,
+            });
+            if expanded {
+                self.list(&path, depth.saturating_add(1), entries);
+            }
+        }
+    }
+
+    pub fn is_expanded(&self, dir: &Path) -> bool {
+        self.expanded.contains(dir)
+    }
+
+    //also expands the directories above it, so it can be seen
+    pub fn expand(&mut self, dir: &Path) {
+        let mut dir = Some(dir);
+        while let Some(path) = dir.filter(|path| *path != self.root && !path.as_os_str().is_empty()) {
+            self.expanded.insert(path.to_path_buf());
+            dir = path.parent();
+        }
+    }
+
+    pub fn collapse(&mut self, dir: &Path) {
+        self.expanded.remove(dir);
+    }
+
+    pub fn line(&self, entry: &Entry) -> String {
+        let name = entry
+            .path
+            .file_name()
+            .map_or_else(|| entry.path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let indent = "  ".repeat(entry.depth);
+        if !entry.is_dir {
+            format!("{indent}  {name}")
+        } else if self.is_expanded(&entry.path) {
+            format!("{indent}▾ {name}/")
+        } else {
+            format!("{indent}▸ {name}/")
+        }
+    }
+}
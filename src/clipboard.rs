@@ -0,0 +1,131 @@
+use crate::Terminal;
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+//where copied text goes besides the internal register
+pub enum Provider {
+    //the terminal's clipboard through the OSC 52 escape sequence
+    Osc52,
+    //external programs like wl-copy or xclip, paste is optional
+    Command {
+        copy: Vec<String>,
+        paste: Option<Vec<String>>,
+    },
+    Register,
+}
+
+//the register always holds the last copied text, so pasting still works
+//when the provider can only be written to or stops working
+pub struct Clipboard {
+    provider: Provider,
+    register: String,
+}
+
+impl Clipboard {
+    //the provider is "osc52", "register", a copy command, or "auto" to look
+    //for a known clipboard tool; KIBI_CLIPBOARD takes precedence over the
+    //`clipboard` setting from the config
+    pub fn new(configured: &str) -> Self {
+        let setting = env::var("KIBI_CLIPBOARD")
+            .ok()
+            .filter(|setting| !setting.trim().is_empty())
+            .unwrap_or_else(|| configured.to_string());
+        let provider = match setting.trim() {
+            "" | "auto" => Provider::detect(),
+            "osc52" => Provider::Osc52,
+            "register" => Provider::Register,
+            command => Provider::Command {
+                copy: command.split_whitespace().map(String::from).collect(),
+                paste: None,
+            },
+        };
+        Self {
+            provider,
+            register: String::new(),
+        }
+    }
+
+    //the text is always kept in the register, an error means the provider failed
+    pub fn copy(&mut self, text: &str) -> Result<(), io::Error> {
+        self.register = text.to_string();
+        match &self.provider {
+            Provider::Osc52 => Terminal::set_clipboard(text),
+            Provider::Command { copy, .. } => run_copy(copy, text),
+            Provider::Register => Ok(()),
+        }
+    }
+
+    pub fn paste(&self) -> String {
+        if let Provider::Command { paste: Some(paste), .. } = &self.provider {
+            if let Ok(text) = run_paste(paste) {
+                return text.replace("\r\n", "\n");
+            }
+        }
+        self.register.clone()
+    }
+}
+
+impl Provider {
+    fn detect() -> Self {
+        let tools: [(&str, &[&str], &[&str]); 4] = [
+            ("WAYLAND_DISPLAY", &["wl-copy"], &["wl-paste", "--no-newline"]),
+            ("DISPLAY", &["xclip", "-selection", "clipboard"], &["xclip", "-selection", "clipboard", "-o"]),
+            ("DISPLAY", &["xsel", "--clipboard", "--input"], &["xsel", "--clipboard", "--output"]),
+            ("", &["pbcopy"], &["pbpaste"]),
+        ];
+        for (variable, copy, paste) in tools {
+            let has_display = variable.is_empty() || env::var_os(variable).is_some();
+            if has_display && copy.first().map_or(false, |program| in_path(program)) {
+                return Self::Command {
+                    copy: copy.iter().map(|arg| (*arg).to_string()).collect(),
+                    paste: Some(paste.iter().map(|arg| (*arg).to_string()).collect()),
+                };
+            }
+        }
+        Self::Osc52
+    }
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH").map_or(false, |paths| {
+        env::split_paths(&paths).any(|path| Path::new(&path).join(program).is_file())
+    })
+}
+
+fn run_copy(command: &[String], text: &str) -> Result<(), io::Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty clipboard command"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, "clipboard command failed"))
+    }
+}
+
+fn run_paste(command: &[String]) -> Result<String, io::Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty clipboard command"))?;
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, "clipboard command failed"))
+    }
+}
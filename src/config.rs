@@ -0,0 +1,206 @@
+use crate::keymap::Keymap;
+use crossterm::style::Color;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    //distance from the cursor's line
+    Relative,
+    //relative, except for the cursor's line which shows its own number
+    Hybrid,
+}
+
+//editor settings, read from `key = value` lines where later files override
+//earlier ones: the system-wide file, the user's file, then the project's file
+pub struct Config {
+    pub tab_width: usize,
+    //whether Tab inserts spaces up to the next tab stop instead of a tab
+    pub soft_tabs: bool,
+    pub message_timeout: Duration,
+    pub status_fg: Color,
+    pub status_bg: Color,
+    //status bar of the windows that don't have focus
+    pub inactive_status_bg: Color,
+    pub selection_bg: Color,
+    pub show_help: bool,
+    //whether long rows continue on the next screen lines instead of scrolling sideways
+    pub soft_wrap: bool,
+    pub line_numbers: LineNumbers,
+    pub line_number_fg: Color,
+    pub current_line_number_fg: Color,
+    //whether plain keys are vi commands until an insert command is typed
+    pub vi_mode: bool,
+    //"auto", "osc52", "register" or a copy command, KIBI_CLIPBOARD overrides it
+    pub clipboard: String,
+    //whether saving keeps the previous version of the file as `file~`
+    pub backup: bool,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            soft_tabs: true,
+            message_timeout: Duration::from_secs(5),
+            status_fg: Color::Black,
+            status_bg: Color::Green,
+            inactive_status_bg: Color::Grey,
+            selection_bg: Color::DarkGrey,
+            show_help: true,
+            soft_wrap: false,
+            line_numbers: LineNumbers::Off,
+            line_number_fg: Color::DarkGrey,
+            current_line_number_fg: Color::Yellow,
+            vi_mode: false,
+            clipboard: "auto".to_string(),
+            backup: false,
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    //invalid lines are skipped and returned as messages instead of failing,
+    //so a typo in the config never keeps kibi from starting
+    pub fn load() -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut errors = Vec::new();
+        for (path, trusted) in paths() {
+            match fs::read_to_string(&path) {
+                Ok(text) => config.parse(&text, &path, trusted, &mut errors),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                Err(error) => errors.push(format!("{}: {error}", path.display())),
+            }
+        }
+        (config, errors)
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn parse(&mut self, text: &str, path: &Path, trusted: bool, errors: &mut Vec<String>) {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => self.set(key.trim(), value.trim(), trusted),
+                None => Err(format!("expected `key = value`, found `{line}`")),
+            };
+            if let Err(message) = result {
+                errors.push(format!("{}:{}: {message}", path.display(), number + 1));
+            }
+        }
+    }
+
+    //`trusted` is false for the project's file, which comes with whatever
+    //directory kibi is started in and so can't choose programs to run
+    fn set(&mut self, key: &str, value: &str, trusted: bool) -> Result<(), String> {
+        match key {
+            "tab_width" => {
+                let tab_width = parse_number(value)?;
+                if tab_width == 0 {
+                    return Err("tab_width must be at least 1".to_string());
+                }
+                self.tab_width = tab_width;
+            }
+            "soft_tabs" => self.soft_tabs = parse_bool(value)?,
+            "message_timeout" => self.message_timeout = Duration::from_secs(parse_number(value)?),
+            "status_fg" => self.status_fg = parse_color(value)?,
+            "status_bg" => self.status_bg = parse_color(value)?,
+            "inactive_status_bg" => self.inactive_status_bg = parse_color(value)?,
+            "selection_bg" => self.selection_bg = parse_color(value)?,
+            "show_help" => self.show_help = parse_bool(value)?,
+            "soft_wrap" => self.soft_wrap = parse_bool(value)?,
+            "line_numbers" => self.line_numbers = parse_line_numbers(value)?,
+            "line_number_fg" => self.line_number_fg = parse_color(value)?,
+            "current_line_number_fg" => self.current_line_number_fg = parse_color(value)?,
+            "vi_mode" => self.vi_mode = parse_bool(value)?,
+            "clipboard" => {
+                if !trusted && !matches!(value, "auto" | "osc52" | "register") {
+                    return Err(
+                        "a clipboard command can only be set in the system or user config".to_string()
+                    );
+                }
+                self.clipboard = value.to_string();
+            }
+            "backup" => self.backup = parse_bool(value)?,
+            //`bind ctrl-x ctrl-s = save` binds a key sequence to a command
+            _ => match key.strip_prefix("bind ") {
+                Some(keys) => self.keymap.bind(keys, value)?,
+                None => return Err(format!("unknown key `{key}`")),
+            },
+        }
+        Ok(())
+    }
+}
+
+//the system-wide file, $XDG_CONFIG_HOME/kibi/config (or ~/.config/kibi/config)
+//and .kibi/config in the current directory, in the order they are applied,
+//along with whether they belong to the system or the user rather than the project
+fn paths() -> Vec<(PathBuf, bool)> {
+    let mut paths = vec![(PathBuf::from("/etc/kibi/config"), true)];
+    let user_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    if let Some(dir) = user_dir {
+        paths.push((dir.join("kibi").join("config"), true));
+    }
+    paths.push((Path::new(".kibi").join("config"), false));
+    paths
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected true or false, found `{value}`")),
+    }
+}
+
+fn parse_line_numbers(value: &str) -> Result<LineNumbers, String> {
+    match value {
+        "off" | "false" | "no" => Ok(LineNumbers::Off),
+        "absolute" | "true" | "yes" | "on" => Ok(LineNumbers::Absolute),
+        "relative" => Ok(LineNumbers::Relative),
+        "hybrid" => Ok(LineNumbers::Hybrid),
+        _ => Err(format!("expected off, absolute, relative or hybrid, found `{value}`")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, found `{value}`"))
+}
+
+//a colour name like `dark_grey`, `reset` for the terminal's default or `#rrggbb`
+fn parse_color(value: &str) -> Result<Color, String> {
+    let error = || format!("unknown colour `{value}`");
+    if value == "reset" {
+        return Ok(Color::Reset);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(error());
+        }
+        let channel = |range| {
+            hex.get(range)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(error)
+        };
+        return Ok(Color::Rgb {
+            r: channel(0..2)?,
+            g: channel(2..4)?,
+            b: channel(4..6)?,
+        });
+    }
+    Color::try_from(value).map_err(|()| error())
+}
@@ -0,0 +1,79 @@
+//line based diff used to preview recovered changes
+
+//changed regions larger than this are shown as a whole replacement
+//instead of computing the longest common subsequence
+const MAX_LCS_CELLS: usize = 4_000_000;
+const CONTEXT: usize = 3;
+
+//returns the diff as lines prefixed with ' ', '-' or '+'
+#[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+pub fn lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.split('\n').collect();
+    let new: Vec<&str> = new.split('\n').collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut result = Vec::new();
+    if old_middle.is_empty() && new_middle.is_empty() {
+        return result;
+    }
+
+    result.push(format!("@@ line {} @@", prefix + 1));
+    for line in &old[prefix.saturating_sub(CONTEXT)..prefix] {
+        result.push(format!(" {line}"));
+    }
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_LCS_CELLS {
+        result.extend(old_middle.iter().map(|line| format!("-{line}")));
+        result.extend(new_middle.iter().map(|line| format!("+{line}")));
+    } else {
+        result.extend(middle(old_middle, new_middle));
+    }
+    let end = old.len() - suffix;
+    for line in &old[end..(end + CONTEXT).min(old.len())] {
+        result.push(format!(" {line}"));
+    }
+    result
+}
+
+#[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+fn middle(old: &[&str], new: &[&str]) -> Vec<String> {
+    //lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0_usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            result.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            result.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    result
+}
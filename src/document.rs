@@ -1,44 +1,196 @@
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 use crate::Row;
-use crate::editor::Position;
-use std::io::{Write, Error};
+use crate::editor::{Position, SearchDirection};
+use crate::FileType;
+use crate::history::{Edit, History};
+use crate::swap;
+use std::io::{BufWriter, Write, Error};
+use regex::Regex;
+use im::Vector;
 
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
+}
+
+//limit for resolving chains of symlinks when saving
+const MAX_SYMLINKS: usize = 40;
+
+//rows are kept in a balanced tree so line lookups, inserts and removals
+//stay logarithmic in the number of lines
 #[derive(Default)]
 pub struct Document {
-    rows: Vec<Row>,
+    rows: Vector<Row>,
     pub filename: Option<String>,
+    file_type: FileType,
+    history: History,
+    saved_revision: Option<usize>,
+    //line ending used for new lines, every row remembers its own ending
+    line_ending: LineEnding,
+    mixed_line_endings: bool,
+    missing_final_newline: bool,
+    //changes that are not recorded in the history, like converting line endings
     dirty: bool,
+    //keep the previous version as `file~` when saving
+    backup: bool,
+    //swap file holding the unsaved changes and the state it was written at
+    swap_file: Option<PathBuf>,
+    swapped_state: Option<(Option<usize>, bool)>,
+    //rows before `highlighted_until` are highlighted for the state they start in,
+    //rows in `edited_until..checked_until` were highlighted before the last edits
+    //and are still right if they start in the same state, the rest never were
+    highlighted_until: usize,
+    edited_until: usize,
+    checked_until: usize,
+    //the row and columns of the search match shown by the last highlight
+    shown_match: Option<(usize, usize, usize)>,
 }
 
 impl Document {
+    //a document without a file, for lists and previews shown in place of the text
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            rows: text.split('\n').map(Row::from).collect(),
+            ..Self::default()
+        }
+    }
+
     pub fn open(filename: &str) -> Result<Self, std::io::Error> {
         let contents = fs::read_to_string(filename)?;
-        let mut rows = Vec::new();
-        for value in contents.lines() {
-            rows.push(Row::from(value));
+        let (mut rows, crlf_count) = read_rows(&contents);
+
+        //the last row has no ending of its own unless the file ends with a newline,
+        //an empty file has none either so it stays empty when saved
+        let missing_final_newline = !contents.ends_with('\n');
+        let line_count = if missing_final_newline {
+            rows.len().saturating_sub(1)
+        } else {
+            rows.len()
+        };
+        let line_ending = if crlf_count > line_count.saturating_sub(crlf_count) {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        };
+        if missing_final_newline {
+            if let Some(row) = rows.back_mut() {
+                row.set_ending(line_ending);
+            }
         }
+
         Ok(Self { 
             rows,
             filename: Some(filename.to_string()),
+            file_type: FileType::from(filename),
+            history: History::default(),
+            saved_revision: None,
+            line_ending,
+            mixed_line_endings: crlf_count > 0 && crlf_count < line_count,
+            missing_final_newline,
             dirty: false,
+            backup: false,
+            swap_file: None,
+            swapped_state: None,
+            highlighted_until: 0,
+            edited_until: 0,
+            checked_until: 0,
+            shown_match: None,
         })
     }
 
     pub fn insert(&mut self, pos: &Position, c: char) {
-        if pos.y > self.rows.len() {
-            return;
+        self.insert_str(pos, c.encode_utf8(&mut [0; 4]));
+    }
+
+    //inserts text that may span several lines, returns the position after it
+    pub fn insert_str(&mut self, pos: &Position, text: &str) -> Position {
+        if pos.y > self.rows.len() || text.is_empty() {
+            return *pos;
         }
 
-        self.dirty = true;
-        if pos.y == self.rows.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row);
+        //typing past the last row appends a new line to the document
+        let (start, text) = if pos.y == self.rows.len() && !self.rows.is_empty() {
+            #[allow(clippy::arithmetic_side_effects)]
+            let last = self.rows.len() - 1;
+            let start = Position {
+                x: self.rows.get(last).map_or(0, Row::len),
+                y: last,
+            };
+            (start, format!("\n{text}"))
         } else {
-            #[allow(clippy::indexing_slicing)]
-            let row = &mut self.rows[pos.y];
-            row.insert(pos.x, c);
+            (*pos, text.to_string())
+        };
+
+        let start_byte = self.rows.get(start.y).map_or(0, |row| row.byte_index(start.x));
+        let end = self.raw_insert(&start, &text);
+        self.record_insert(start, start_byte, end, text);
+        end
+    }
+
+    //when the text merged with the graphemes around it, the merged graphemes are
+    //recorded as replaced, so undo brings back exactly what was there before
+    #[allow(clippy::arithmetic_side_effects)]
+    fn record_insert(&mut self, start: Position, start_byte: usize, end: Position, text: String) {
+        let merged_before = self
+            .rows
+            .get(start.y)
+            .map_or(false, |row| row.byte_index(start.x) != start_byte);
+        let end_byte = match text.rsplit_once('\n') {
+            Some((_, last)) => last.len(),
+            None => start_byte + text.len(),
+        };
+        let merged_after = self
+            .rows
+            .get(end.y)
+            .map_or(false, |row| row.byte_index(end.x) != end_byte);
+        if !merged_before && !merged_after {
+            self.history.record(Edit::Insert { start, end, text });
+            return;
         }
+
+        let merged_start = Position {
+            x: start.x.saturating_sub(usize::from(merged_before)),
+            y: start.y,
+        };
+        let merged = self.text(&merged_start, &end);
+        let prefix = start_byte - self.rows.get(start.y).map_or(0, |row| row.byte_index(merged_start.x));
+        let before = merged.get(..prefix).unwrap_or_default();
+        let after = merged.get(prefix + text.len()..).unwrap_or_default();
+        self.history.begin(start);
+        self.history.record(Edit::Delete {
+            start: merged_start,
+            end: Position {
+                x: merged_start.x + usize::from(merged_before) + usize::from(merged_after),
+                y: start.y,
+            },
+            text: format!("{before}{after}"),
+        });
+        self.history.record(Edit::Insert {
+            start: merged_start,
+            end,
+            text: merged,
+        });
+        self.history.end(end);
     }
 
     #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
@@ -47,16 +199,48 @@ impl Document {
             return;
         } 
 
-        self.dirty = true;
-        
-        if pos.x == self.rows[pos.y].len() && pos.y + 1 < self.rows.len() {
-            let next_row = self.rows.remove(pos.y + 1);
-            let current_row = &mut self.rows[pos.y];
-            current_row.append(&next_row);
+        let end = if pos.x >= self.rows[pos.y].len() {
+            Position { x: 0, y: pos.y + 1 }
         } else {
-            let row = &mut self.rows[pos.y];
-            row.delete(pos.x);
+            Position { x: pos.x + 1, y: pos.y }
+        };
+        self.delete_range(pos, &end);
+    }
+
+    //the text between start and end, rows are joined with '\n'
+    pub fn text(&self, start: &Position, end: &Position) -> String {
+        let end = self.clamp(end);
+        let mut text = String::new();
+        for y in start.y..=end.y {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            if y > start.y {
+                text.push('\n');
+            }
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            text.push_str(&row.slice(from, to));
         }
+        text
+    }
+
+    //removes the text between start and end, returns the removed text
+    pub fn delete_range(&mut self, start: &Position, end: &Position) -> String {
+        let end = self.clamp(end);
+        if *start >= end {
+            return String::new();
+        }
+
+        let text = self.raw_delete(start, &end);
+        if !text.is_empty() {
+            self.history.record(Edit::Delete {
+                start: *start,
+                end,
+                text: text.clone(),
+            });
+        }
+        text
     }
 
     pub fn new_line(&mut self, pos: &Position) {
@@ -64,31 +248,473 @@ impl Document {
             return;
         }
         if pos.y == self.rows.len() {
-            self.rows.push(Row::default());
-            return;
+            let start = Position {
+                x: self.rows.last().map_or(0, Row::len),
+                y: self.rows.len().saturating_sub(1),
+            };
+            self.insert_str(&start, "\n");
         } else {
-            #[allow(clippy::indexing_slicing)]
-            let new_row = self.rows[pos.y].split(pos.x);
-            #[allow(clippy::arithmetic_side_effects)]
-            self.rows.insert(pos.y + 1, new_row)
+            self.insert_str(pos, "\n");
+        }
+    }
+
+    pub fn begin_transaction(&mut self, cursor: &Position) {
+        self.history.begin(*cursor);
+    }
+
+    pub fn end_transaction(&mut self, cursor: &Position) {
+        self.history.end(*cursor);
+    }
+
+    pub fn close_transactions(&mut self, cursor: &Position) {
+        self.history.close(*cursor);
+    }
+
+    pub fn checkpoint(&mut self, cursor: &Position) {
+        self.history.checkpoint(*cursor);
+    }
+
+    //reverts the last transaction and returns the cursor position before it
+    pub fn undo(&mut self) -> Option<Position> {
+        let transaction = self.history.pop_undo()?;
+        for edit in transaction.edits.iter().rev() {
+            match edit {
+                Edit::Insert { start, end, .. } => {
+                    self.raw_delete(start, end);
+                }
+                Edit::Delete { start, text, .. } => {
+                    self.raw_insert(start, text);
+                }
+            }
+        }
+        let cursor = transaction.cursor_before;
+        self.history.push_redo(transaction);
+        Some(cursor)
+    }
+
+    //reapplies the last undone transaction and returns the cursor position after it
+    pub fn redo(&mut self) -> Option<Position> {
+        let transaction = self.history.pop_redo()?;
+        for edit in &transaction.edits {
+            match edit {
+                Edit::Insert { start, text, .. } => {
+                    self.raw_insert(start, text);
+                }
+                Edit::Delete { start, end, .. } => {
+                    self.raw_delete(start, end);
+                }
+            }
+        }
+        let cursor = transaction.cursor_after;
+        self.history.push_undo(transaction);
+        Some(cursor)
+    }
+
+    //the end is found by the bytes of the text, the row's length can't be used
+    //when the text merges with the grapheme after it, like a combining mark
+    #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+    fn raw_insert(&mut self, pos: &Position, text: &str) -> Position {
+        if self.rows.is_empty() {
+            let mut row = Row::default();
+            row.set_ending(self.line_ending);
+            self.rows.push_back(row);
+        }
+
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        let mut tail: Option<Row> = None;
+        if text.contains('\n') {
+            tail = Some(self.rows[pos.y].split(pos.x));
+            self.rows[pos.y].set_ending(self.line_ending);
+        }
+        let mut end = *pos;
+        let mut end_byte = self.rows[pos.y].byte_index(pos.x) + first.len();
+        self.rows[pos.y].insert(pos.x, first);
+
+        for line in lines {
+            end.y += 1;
+            let mut row = Row::from(line);
+            row.set_ending(self.line_ending);
+            self.rows.insert(end.y, row);
+            end_byte = line.len();
         }
+        if let Some(tail) = tail {
+            self.rows[end.y].append(&tail);
+        }
+        end.x = self.rows[end.y].grapheme_index(end_byte);
+        self.edited_rows(pos.y, pos.y, end.y);
+        end
     }
 
+    #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+    fn raw_delete(&mut self, start: &Position, end: &Position) -> String {
+        self.edited_rows(start.y, end.y, start.y);
+        if start.y == end.y {
+            return self.rows[start.y].delete(start.x, end.x);
+        }
+
+        let first = &mut self.rows[start.y];
+        let mut removed = first.delete(start.x, first.len());
+        let drained = self.rows.slice(start.y + 1..=end.y);
+        for (i, mut row) in drained.into_iter().enumerate() {
+            removed.push('\n');
+            if start.y + 1 + i == end.y {
+                let tail = row.split(end.x);
+                removed.extend(row.chunks());
+                self.rows[start.y].append(&tail);
+            } else {
+                removed.extend(row.chunks());
+            }
+        }
+        removed
+    }
+
+    //positions past the end of a row or the document are moved back inside it
+    fn clamp(&self, pos: &Position) -> Position {
+        match self.rows.get(pos.y) {
+            Some(row) => Position {
+                x: pos.x.min(row.len()),
+                y: pos.y,
+            },
+            None => Position {
+                x: self.rows.last().map_or(0, Row::len),
+                y: self.rows.len().saturating_sub(1),
+            },
+        }
+    }
+
+    //writes to a temporary file next to the target and renames it over the target,
+    //so a failed save never leaves a truncated file behind
     pub fn save(&mut self) -> Result<(), Error> {
         if let Some(name) = &self.filename {
-            let mut file = fs::File::create(name)?;
-            for (i, row) in self.rows.iter().enumerate() {
-                file.write_all(row.as_bytes())?;
-                if i != self.rows.len() - 1 {
-                    file.write_all(b"\n")?;
+            let target = resolve_symlinks(Path::new(name));
+            let temp = temp_path(&target);
+            if let Err(error) = self.write_to(&temp, &target) {
+                _ = fs::remove_file(&temp);
+                return Err(error);
+            }
+            if self.backup && target.exists() {
+                if let Err(error) = fs::copy(&target, backup_path(&target)) {
+                    _ = fs::remove_file(&temp);
+                    return Err(error);
                 }
             }
+            if let Err(error) = fs::rename(&temp, &target) {
+                _ = fs::remove_file(&temp);
+                return Err(error);
+            }
+            sync_directory(&target);
+
+            self.file_type = FileType::from(name);
+            for row in self.rows.iter_mut() {
+                row.unhighlight();
+            }
+            self.highlighted_until = 0;
+            self.edited_until = 0;
+            self.checked_until = 0;
+            self.saved_revision = self.history.revision();
             self.dirty = false;
+            self.remove_swap();
         }
 
         Ok(())
     }
 
+    //writes the unsaved contents to the swap file when they changed since the
+    //last call, and removes the swap file once the document is clean again
+    pub fn update_swap(&mut self) -> Result<(), Error> {
+        let Some(name) = &self.filename else {
+            return Ok(());
+        };
+        if !self.is_dirty() {
+            self.remove_swap();
+            return Ok(());
+        }
+
+        let state = (self.history.revision(), self.dirty);
+        let path = swap::path(name);
+        if self.swapped_state == Some(state) && self.swap_file.as_ref() == Some(&path) {
+            return Ok(());
+        }
+        self.remove_swap();
+
+        let mut writer = swap::create(&path)?;
+        self.swap_file = Some(path);
+        self.write_rows(&mut writer)?;
+        writer.flush()?;
+        self.swapped_state = Some(state);
+        Ok(())
+    }
+
+    pub fn remove_swap(&mut self) {
+        if let Some(path) = self.swap_file.take() {
+            swap::remove(&path);
+        }
+        self.swapped_state = None;
+    }
+
+    //contents of a swap file left behind by a previous session
+    pub fn stale_swap(&self) -> Option<String> {
+        self.filename
+            .as_ref()
+            .and_then(|name| swap::read_stale(&swap::path(name)))
+    }
+
+    pub fn discard_stale_swap(&self) {
+        if let Some(name) = &self.filename {
+            swap::remove(&swap::path(name));
+        }
+    }
+
+    //replaces the whole text with recovered contents as one undoable change,
+    //the recovered line endings are restored outside of the history
+    pub fn recover(&mut self, contents: &str) {
+        let (recovered, _) = read_rows(contents);
+        let text: Vec<String> = recovered.iter().map(Row::contents).collect();
+        let start = Position::default();
+        self.begin_transaction(&start);
+        let end = Position {
+            x: self.rows.last().map_or(0, Row::len),
+            y: self.rows.len().saturating_sub(1),
+        };
+        self.delete_range(&start, &end);
+        self.insert_str(&start, &text.join("\n"));
+        self.end_transaction(&start);
+
+        let missing_final_newline = !contents.ends_with('\n');
+        let last = recovered.len().saturating_sub(1);
+        for (i, (row, recovered)) in self.rows.iter_mut().zip(recovered.iter()).enumerate() {
+            let ending = if i == last && missing_final_newline {
+                self.line_ending
+            } else {
+                recovered.ending()
+            };
+            if row.ending() != ending {
+                row.set_ending(ending);
+                self.dirty = true;
+            }
+        }
+        if self.missing_final_newline != missing_final_newline {
+            self.missing_final_newline = missing_final_newline;
+            self.dirty = true;
+        }
+    }
+
+    //the text with rows joined by '\n', regardless of the line endings
+    pub fn contents(&self) -> String {
+        let mut contents = String::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                contents.push('\n');
+            }
+            contents.extend(row.chunks());
+        }
+        contents
+    }
+
+    //the text as it is written to the file, with the ending of every row
+    pub fn file_contents(&self) -> String {
+        let mut contents = Vec::new();
+        _ = self.write_rows(&mut contents);
+        String::from_utf8(contents).unwrap_or_default()
+    }
+
+    fn write_rows(&self, writer: &mut impl Write) -> Result<(), Error> {
+        for (i, row) in self.rows.iter().enumerate() {
+            for chunk in row.chunks() {
+                writer.write_all(chunk.as_bytes())?;
+            }
+            if i.saturating_add(1) != self.rows.len() || !self.missing_final_newline {
+                writer.write_all(row.ending().as_str().as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_to(&self, path: &Path, target: &Path) -> Result<(), Error> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        if let Ok(metadata) = fs::metadata(target) {
+            file.set_permissions(metadata.permissions())?;
+            preserve_owner(&file, &metadata);
+        }
+
+        let mut writer = BufWriter::new(file);
+        self.write_rows(&mut writer)?;
+        let file = writer.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()
+    }
+
+    pub fn set_backup(&mut self, backup: bool) {
+        self.backup = backup;
+    }
+
+    pub fn backup(&self) -> bool {
+        self.backup
+    }
+
+    //searches from `at` in the given direction, wrapping around the document
+    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+        let len = self.rows.len();
+        if len == 0 {
+            return None;
+        }
+        let mut position = self.clamp(at);
+        for _ in 0..=len {
+            let row = self.rows.get(position.y)?;
+            if let Some(x) = row.find(query, position.x, direction) {
+                position.x = x;
+                return Some(position);
+            }
+            match direction {
+                SearchDirection::Forward => {
+                    position.y = position.y.saturating_add(1) % len;
+                    position.x = 0;
+                }
+                SearchDirection::Backward => {
+                    position.y = position.y.checked_sub(1).unwrap_or(len.saturating_sub(1));
+                    position.x = self.rows.get(position.y).map_or(0, Row::len);
+                }
+            }
+        }
+        None
+    }
+
+    //finds the next regex match at or after `at`, matches never span lines
+    pub fn find_regex(&self, regex: &Regex, at: &Position) -> Option<(Position, Position)> {
+        for y in at.y..self.rows.len() {
+            let row = self.rows.get(y)?;
+            let x = if y == at.y { at.x } else { 0 };
+            if let Some((start, end)) = row.find_regex(regex, x) {
+                return Some((Position { x: start, y }, Position { x: end, y }));
+            }
+        }
+        None
+    }
+
+    pub fn expand_regex(&self, regex: &Regex, at: &Position, replacement: &str) -> String {
+        self.rows
+            .get(at.y)
+            .map_or_else(String::new, |row| row.expand_regex(regex, at.x, replacement))
+    }
+
+    //replaces the text between start and end, returns the position after the new text
+    pub fn replace(&mut self, start: &Position, end: &Position, text: &str) -> Position {
+        self.begin_transaction(start);
+        self.delete_range(start, end);
+        let end = if text.is_empty() {
+            *start
+        } else {
+            self.insert_str(start, text)
+        };
+        self.end_transaction(&end);
+        end
+    }
+
+    //highlights rows up to `until`, `highlighted` marks a search match; rows are
+    //looked at again from the first edited one, and only until the rows after the
+    //edits start in the same multiline comment state as before
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn highlight(&mut self, highlighted: Option<(Position, Position)>, until: Option<usize>) {
+        let len = self.rows.len();
+        let until = until.map_or(len, |until| until.min(len));
+        let opts = self.file_type.highlighting_options();
+        let shown_match = highlighted.map(|(start, end)| (start.y, start.x, end.x));
+        let match_range = |y: usize| {
+            shown_match
+                .filter(|(match_y, ..)| *match_y == y)
+                .map(|(_, start, end)| (start, end))
+        };
+
+        //a moved match only changes the colours of its rows, not their comment state
+        if shown_match != self.shown_match {
+            for (y, ..) in self.shown_match.into_iter().chain(shown_match) {
+                if let Some(row) = self.rows.get_mut(y) {
+                    row.set_match(opts, match_range(y));
+                }
+            }
+            self.shown_match = shown_match;
+        }
+
+        let mut y = self.highlighted_until.min(len);
+        let mut in_comment = y
+            .checked_sub(1)
+            .and_then(|y| self.rows.get(y))
+            .map_or(false, Row::ends_in_comment);
+        let mut rows = self.rows.focus_mut();
+        while let Some(row) = rows.get_mut(y) {
+            if y >= self.edited_until && y < self.checked_until && row.is_highlighted_for(in_comment) {
+                //the rows up to `checked_until` haven't changed since they were highlighted
+                y = self.checked_until;
+                in_comment = rows.get_mut(y - 1).map_or(false, |row| row.ends_in_comment());
+                continue;
+            }
+            if y >= until {
+                break;
+            }
+            in_comment = row.highlight(opts, match_range(y), in_comment);
+            y += 1;
+        }
+        self.highlighted_until = y.min(len);
+        self.checked_until = self.checked_until.max(self.highlighted_until);
+    }
+
+    //keeps the highlighting watermarks in place after the rows `start..=old_end`
+    //were replaced by `start..=new_end`
+    #[allow(clippy::arithmetic_side_effects)]
+    fn edited_rows(&mut self, start: usize, old_end: usize, new_end: usize) {
+        let shift = |y: usize| if y > old_end { y - old_end + new_end } else { y };
+        //edits that were highlighted since don't count anymore, but a highlight
+        //that stopped before `checked_until` may have left the row it stopped
+        //at in a different comment state than the row before it
+        let edited_until = if self.edited_until > self.highlighted_until {
+            shift(self.edited_until)
+        } else {
+            0
+        };
+        let stopped_at = if self.highlighted_until < self.checked_until {
+            shift(self.highlighted_until)
+        } else {
+            0
+        };
+        self.highlighted_until = self.highlighted_until.min(start);
+        self.edited_until = edited_until.max(stopped_at).max(new_end + 1);
+        self.checked_until = shift(self.checked_until).max(self.edited_until);
+    }
+
+    //converts every line, the conversion is not part of the undo history
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        for row in self.rows.iter_mut() {
+            if row.ending() != line_ending {
+                row.set_ending(line_ending);
+                self.dirty = true;
+            }
+        }
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.dirty = true;
+        }
+        self.mixed_line_endings = false;
+    }
+
+    pub fn line_ending_name(&self) -> &'static str {
+        if self.mixed_line_endings {
+            "Mixed"
+        } else {
+            self.line_ending.name()
+        }
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        !self.missing_final_newline
+    }
+
+    pub fn file_type(&self) -> &'static str {
+        self.file_type.name()
+    }
+
     pub fn row(&self, index: usize) -> Option<&Row> {
         self.rows.get(index)
     }
@@ -97,11 +723,129 @@ impl Document {
         self.rows.is_empty()
     }
 
+    //for changes to the file rather than the text, like it being deleted
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty || self.history.revision() != self.saved_revision
     }
 
     pub fn len(&self) -> usize {
         self.rows.len()
     }
-}
\ No newline at end of file
+}
+
+//splits file contents into rows that remember their line ending,
+//also returns how many of them end with "\r\n"
+fn read_rows(contents: &str) -> (Vector<Row>, usize) {
+    let mut rows = Vector::new();
+    let mut crlf_count: usize = 0;
+    let mut lines = contents.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_none() && line.is_empty() {
+            break;
+        }
+        let row = match line.strip_suffix('\r') {
+            Some(line) if lines.peek().is_some() => {
+                crlf_count = crlf_count.saturating_add(1);
+                let mut row = Row::from(line);
+                row.set_ending(LineEnding::CrLf);
+                row
+            }
+            _ => Row::from(line),
+        };
+        rows.push_back(row);
+    }
+    (rows, crlf_count)
+}
+
+//saving through a symlink replaces the file it points to, not the link
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINKS {
+        match fs::read_link(&path) {
+            Ok(target) => {
+                path = path
+                    .parent()
+                    .map_or_else(|| target.clone(), |parent| parent.join(&target));
+            }
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    target.with_file_name(format!(".{name}.kibi-{}.tmp", process::id()))
+}
+
+fn backup_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push("~");
+    PathBuf::from(name)
+}
+
+//ownership can only be kept when we are allowed to change it, failing is fine
+#[cfg(unix)]
+fn preserve_owner(file: &fs::File, metadata: &fs::Metadata) {
+    use std::os::unix::fs::{fchown, MetadataExt};
+    _ = fchown(file, Some(metadata.uid()), Some(metadata.gid()));
+}
+
+#[cfg(not(unix))]
+fn preserve_owner(_file: &fs::File, _metadata: &fs::Metadata) {}
+
+//makes the rename itself durable
+#[cfg(unix)]
+fn sync_directory(target: &Path) {
+    let directory = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(directory) = fs::File::open(directory) {
+        _ = directory.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_target: &Path) {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_before_combining_mark() {
+        let mut document = Document::from_text("\u{301}x");
+        let end = document.insert_str(&Position::default(), "a\n");
+        assert_eq!(end, Position { x: 0, y: 1 });
+        assert_eq!(document.contents(), "a\n\u{301}x");
+        document.undo();
+        assert_eq!(document.contents(), "\u{301}x");
+        document.redo();
+        assert_eq!(document.contents(), "a\n\u{301}x");
+    }
+
+    #[test]
+    fn insert_merging_with_combining_mark() {
+        let mut document = Document::from_text("\u{301}x");
+        let end = document.insert_str(&Position::default(), "a");
+        assert_eq!(end, Position { x: 1, y: 0 });
+        assert_eq!(document.contents(), "a\u{301}x");
+        document.undo();
+        assert_eq!(document.contents(), "\u{301}x");
+        document.redo();
+        assert_eq!(document.contents(), "a\u{301}x");
+
+        let mut document = Document::from_text("ex");
+        let end = document.insert_str(&Position { x: 1, y: 0 }, "\u{301}\ny");
+        assert_eq!(end, Position { x: 1, y: 1 });
+        assert_eq!(document.contents(), "e\u{301}\nyx");
+        document.undo();
+        assert_eq!(document.contents(), "ex");
+    }
+}
@@ -1,34 +1,137 @@
 use crate::Terminal;
 use crate::Document;
+use crate::document::LineEnding;
 use crate::Row;
+use crate::diff;
+use crate::browser::{Entry, Tree};
+use crate::finder;
+use crate::clipboard::Clipboard;
+use crate::config::{Config, LineNumbers};
+use crate::keymap::{self, Chord, Command, Lookup};
+use crate::layout::{Layout, Rect, SplitKind};
+use crate::rope;
+use crate::vi::{self, Action, Mode, Motion, Operator, Parsed, Range, Vi};
+use std::cmp;
 use std::env;
+use std::fs;
 use std::io;
+use std::mem;
+use std::path::{Component, Path, PathBuf};
 use std::time::{Instant, Duration};
-use crossterm::style::Color;
-use crossterm::{self, execute, style, cursor, terminal, event::{
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use crossterm::{self, execute, style, cursor, event::{
     self,
-    Event::Key,
+    Event::{Key, Mouse, Paste, Resize},
     KeyCode::{self, *},
     KeyEventKind, KeyEvent, KeyModifiers,
+    MouseButton, MouseEvent, MouseEventKind,
 }};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+//how often unsaved changes are written to the swap file
+const SWAP_INTERVAL: Duration = Duration::from_secs(2);
+//two clicks on the same spot within this time select the word under them
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+const SCROLL_LINES: usize = 3;
+//percent a window grows or shrinks by at a time
+const RESIZE_STEP: usize = 5;
+//most files the open file popup shows at once
+const POPUP_HEIGHT: usize = 10;
+const BROWSER_HELP: &str = "Enter open, arrows fold, n new, r rename, d delete, ESC close";
 
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
     cursor_position: Position,
     offset: Position,
+    //with soft wrap, the screen lines of the top row that are scrolled out of view
+    wrap_offset: usize,
     document: Document,
     status_message: StatusMessage,
+    highlighted_match: Option<(Position, Position)>,
+    last_swap: Instant,
+    //the selection spans from the anchor to the cursor
+    selection_anchor: Option<Position>,
+    clipboard: Clipboard,
+    last_click: Option<(Instant, Position)>,
+    config: Config,
+    //the keys typed so far of a sequence like `ctrl-x ctrl-s`
+    pending_keys: Vec<Chord>,
+    //every open buffer, the one being edited lives in the fields above
+    //and its slot only holds an empty placeholder
+    buffers: Vec<Buffer>,
+    current_buffer: usize,
+    //the text area of the focused window, without its status bar
+    area: Rect,
+    //every window, the focused one lives in the fields above like the current buffer
+    windows: Vec<Window>,
+    current_window: usize,
+    layout: Layout,
+    separators: Vec<Rect>,
+    //a picker or the diff is shown in place of the focused window's document
+    temporary_view: bool,
+    //lines shown above the message bar, the first one closest to it
+    popup: Vec<String>,
+    popup_selected: usize,
+    //a directory given on the command line, browsed once the editor runs
+    browse_root: Option<PathBuf>,
+    vi: Vi,
 }
 
+//a view of a buffer in part of the screen
 #[derive(Default)]
+struct Window {
+    buffer: usize,
+    cursor_position: Position,
+    offset: Position,
+    wrap_offset: usize,
+    selection_anchor: Option<Position>,
+    area: Rect,
+}
+
+//a document together with the state of its view
+#[derive(Default)]
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+    wrap_offset: usize,
+    selection_anchor: Option<Position>,
+}
+
+enum ReplaceChoice {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+//positions are ordered by line first, then by column
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.y.cmp(&other.y).then(self.x.cmp(&other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 pub struct StatusMessage {
     text: String,
     time: Instant,
@@ -45,54 +148,146 @@ impl StatusMessage {
 
 impl Editor {
     pub fn default() -> Self {
-        let mut initial_status = String::from("HELP: Ctrl-S = save, Ctrl-Q = quit");
-        let args: Vec<String> = env::args().collect();
-        let document = if let Some(filename) = args.get(1) {
-            let doc = Document::open(&filename);
-            if let Ok(doc) = doc {
-                doc
+        let (mut config, errors) = Config::load();
+        let mut initial_status = if let Some(error) = errors.first() {
+            let more = errors.len().saturating_sub(1);
+            if more > 0 {
+                format!("ERR: Config {error} (and {more} more)")
             } else {
-                initial_status = format!("ERR: Could not open file: {}", filename);
-                Document::default()
+                format!("ERR: Config {error}")
             }
+        } else if config.show_help {
+            help_message(&config)
         } else {
-            Document::default()
+            String::new()
         };
+        let args: Vec<String> = env::args().skip(1).collect();
+        //--backup keeps the previous version of the file as `file~` on save,
+        //like `backup = true` in the config
+        let backup = config.backup || args.iter().any(|arg| arg == "--backup");
+        //--tab-width=N overrides the tab width from the config
+        if let Some(tab_width) = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--tab-width="))
+            .and_then(|width| width.parse::<usize>().ok())
+            .filter(|width| *width > 0)
+        {
+            config.tab_width = tab_width;
+        }
+        //every file is opened as its own buffer
+        let mut documents = Vec::new();
+        let mut browse_root = None;
+        for arg in args.iter().filter(|arg| !arg.starts_with("--")) {
+            let (filename, location) = split_location(arg);
+            if Path::new(arg).is_dir() {
+                browse_root = browse_root.or_else(|| Some(PathBuf::from(arg)));
+            } else if let Ok(mut document) = Document::open(filename) {
+                document.set_backup(backup);
+                documents.push((document, location));
+            } else {
+                initial_status = format!("ERR: Could not open file: {}", filename);
+            }
+        }
+        let mut documents = documents.into_iter();
+        let (mut document, location) = documents.next().unwrap_or_default();
+        document.set_backup(backup);
 
-        Self {
+        let clipboard = Clipboard::new(&config.clipboard);
+        let mut editor = Self {
             should_quit: false,
             terminal: Terminal::default().expect("failed to initalize terminal"),
             cursor_position: Position::default(),
             offset: Position::default(),
+            wrap_offset: 0,
             document,
             status_message: StatusMessage::from(initial_status),
+            highlighted_match: None,
+            last_swap: Instant::now(),
+            selection_anchor: None,
+            clipboard,
+            last_click: None,
+            config,
+            pending_keys: Vec::new(),
+            buffers: vec![Buffer::default()],
+            current_buffer: 0,
+            area: Rect::default(),
+            windows: vec![Window::default()],
+            current_window: 0,
+            layout: Layout::Window(0),
+            separators: Vec::new(),
+            temporary_view: false,
+            popup: Vec::new(),
+            popup_selected: 0,
+            browse_root,
+            vi: Vi::default(),
+        };
+        editor.layout_windows();
+        if let Some((line, column)) = location {
+            editor.go_to(line.saturating_sub(1), column.saturating_sub(1));
+        }
+        for (document, location) in documents {
+            editor.open_buffer(document);
+            if let Some((line, column)) = location {
+                editor.go_to(line.saturating_sub(1), column.saturating_sub(1));
+            }
         }
+        editor.switch_buffer(0);
+        editor
     }
 
     pub fn run(&mut self) -> Result<(), std::io::Error> {
+        for index in 0..self.buffers.len() {
+            self.switch_buffer(index);
+            self.recover_swap()?;
+        }
+        self.switch_buffer(0);
+        if let Some(root) = self.browse_root.take() {
+            self.browse(root)?;
+        }
         self.refresh_screen()?;
         loop {
-            let event = event::read().unwrap();
-            if let Key(key_event) = event {
-                if let KeyEventKind::Press = key_event.kind {
-                    self.handle_key_press(&key_event)?;
+            //wakes up regularly even without input to keep the swap file current
+            if event::poll(SWAP_INTERVAL)? {
+                match event::read()? {
+                    Key(key_event) => {
+                        if let KeyEventKind::Press = key_event.kind {
+                            self.handle_key_press(&key_event)?;
+                        }
+                        //will now refresh an extra time before quitting
+                        self.refresh_screen()?;
+                    }
+                    Mouse(mouse_event) => {
+                        self.handle_mouse(&mouse_event);
+                        self.refresh_screen()?;
+                    }
+                    Paste(text) => {
+                        self.handle_paste(&text);
+                        self.refresh_screen()?;
+                    }
+                    Resize(width, height) => {
+                        self.resize(width, height);
+                        self.refresh_screen()?;
+                    }
+                    _ => (),
                 }
-            } else {continue;}
-            
-            //will now refresh an extra time before quitting
-            self.refresh_screen()?;
+            }
 
+            if self.last_swap.elapsed() >= SWAP_INTERVAL {
+                self.update_swap();
+            }
+
+            //the terminal itself is restored when the editor is dropped
             if self.should_quit {
-                if let Err(e) = terminal::disable_raw_mode() {
-                    eprintln!("failed to disable raw mode");
-                    return Err(e);
+                self.document.remove_swap();
+                for buffer in &mut self.buffers {
+                    buffer.document.remove_swap();
                 }
                 break Ok(())
             }
         }
     }
         
-    fn refresh_screen(&self) -> Result<(), std::io::Error> {
+    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
 
         //should it move to (1, 1) or (0, 0)? 
         //I think (0, 0)
@@ -104,24 +299,319 @@ impl Editor {
         if self.should_quit {
             Terminal::clear_screen();
         } else {
-            //TODO add error handling
-            self.draw_rows();
-            self.draw_status_bar()?;
+            //every window is drawn while it is entered, the focused one last,
+            //the others are left as they were while a temporary view is shown
+            let focused = self.current_window;
+            if !self.temporary_view {
+                for index in (0..self.windows.len()).filter(|index| *index != focused) {
+                    self.enter_window(index);
+                    self.draw_window(false)?;
+                }
+                self.enter_window(focused);
+            }
+            self.draw_window(true)?;
+            self.draw_separators();
+            self.draw_popup()?;
             self.draw_message_bar();
-            Terminal::cursor_position(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
-                y: self.cursor_position.y.saturating_sub(self.offset.y),
-            })
+            if let Some(position) = self.cursor_screen_position() {
+                Terminal::cursor_position(&position);
+            }
         }
 
-        execute!(io::stdout(), cursor::Show).unwrap();
+        //the cursor stays hidden while the wheel scrolled it out of view
+        if self.should_quit || self.cursor_screen_position().is_some() {
+            execute!(io::stdout(), cursor::Show).unwrap();
+        }
         Terminal::flush()
     }
 
-    fn scroll(&mut self) {
+    //keeps the cursor visible at the new size, the next refresh redraws everything
+    fn resize(&mut self, width: u16, height: u16) {
+        self.terminal.resize(width, height);
+        self.layout_windows();
+        Terminal::clear_screen();
+        self.scroll();
+    }
+
+    fn draw_window(&mut self, focused: bool) -> Result<(), std::io::Error> {
+        //the search match is only shown where the search was made
+        let highlighted_match = if focused { self.highlighted_match } else { None };
+        self.document.highlight(
+            highlighted_match,
+            Some(self.offset.y.saturating_add(self.area.height)),
+        );
+        self.draw_rows()?;
+        self.draw_status_bar(focused)
+    }
+
+    fn draw_separators(&self) {
+        for separator in &self.separators {
+            for y in separator.y..separator.y.saturating_add(separator.height) {
+                Terminal::cursor_position(&Position {x: separator.x, y});
+                print!("│");
+            }
+        }
+    }
+
+    //gives every window its part of the screen, each ends with its status bar
+    fn layout_windows(&mut self) {
+        let size = self.terminal.size();
+        let screen = Rect {
+            x: 0,
+            y: 0,
+            width: size.width as usize,
+            height: (size.height as usize).saturating_add(1),
+        };
+        let mut rects = Vec::new();
+        self.separators.clear();
+        self.layout.rects(screen, &mut rects, &mut self.separators);
+        for (index, rect) in rects {
+            let area = Rect {
+                height: rect.height.saturating_sub(1),
+                ..rect
+            };
+            if index == self.current_window {
+                self.area = area;
+            } else if let Some(window) = self.windows.get_mut(index) {
+                window.area = area;
+            }
+        }
+    }
+
+    //exchanges the view of the focused window with the one in the slot
+    fn swap_window(&mut self, index: usize) {
+        if let Some(window) = self.windows.get_mut(index) {
+            mem::swap(&mut self.cursor_position, &mut window.cursor_position);
+            mem::swap(&mut self.offset, &mut window.offset);
+            mem::swap(&mut self.wrap_offset, &mut window.wrap_offset);
+            mem::swap(&mut self.selection_anchor, &mut window.selection_anchor);
+            mem::swap(&mut self.area, &mut window.area);
+        }
+    }
+
+    //makes the window the one the editor's fields refer to, bringing in its document
+    fn enter_window(&mut self, index: usize) {
+        if index == self.current_window || index >= self.windows.len() {
+            return;
+        }
+        self.swap_window(self.current_window);
+        if let Some(window) = self.windows.get_mut(self.current_window) {
+            window.buffer = self.current_buffer;
+        }
+        let buffer = self.windows.get(index).map_or(self.current_buffer, |window| window.buffer);
+        if buffer != self.current_buffer && buffer < self.buffers.len() {
+            if let Some(slot) = self.buffers.get_mut(self.current_buffer) {
+                mem::swap(&mut self.document, &mut slot.document);
+            }
+            if let Some(slot) = self.buffers.get_mut(buffer) {
+                mem::swap(&mut self.document, &mut slot.document);
+            }
+            self.current_buffer = buffer;
+        }
+        self.swap_window(index);
+        self.current_window = index;
+        self.clamp_view();
+    }
+
+    //another window may have changed the document since the view was left
+    fn clamp_view(&mut self) {
+        let clamp = |document: &Document, position: Position| {
+            let y = cmp::min(position.y, document.len());
+            let x = cmp::min(position.x, document.row(y).map_or(0, Row::len));
+            Position {x, y}
+        };
+        self.cursor_position = clamp(&self.document, self.cursor_position);
+        self.selection_anchor = self.selection_anchor.map(|anchor| clamp(&self.document, anchor));
+    }
+
+    fn focus_window(&mut self, index: usize) {
+        if index == self.current_window {
+            return;
+        }
+        self.leave_document();
+        self.update_swap();
+        self.highlighted_match = None;
+        self.enter_window(index);
+        self.scroll();
+    }
+
+    //the window showing the screen cell, status bars included
+    fn window_at(&self, x: usize, y: usize) -> Option<usize> {
+        (0..self.windows.len()).find(|index| {
+            let area = if *index == self.current_window {
+                self.area
+            } else {
+                self.windows.get(*index).map_or_else(Rect::default, |window| window.area)
+            };
+            Rect {
+                height: area.height.saturating_add(1),
+                ..area
+            }
+            .contains(x, y)
+        })
+    }
+
+    fn split_window(&mut self, kind: SplitKind) {
+        let index = self.windows.len();
+        if !self.layout.split(self.current_window, index, kind) {
+            return;
+        }
+        //the new window starts out as a copy of this one
+        self.windows.push(Window {
+            buffer: self.current_buffer,
+            cursor_position: self.cursor_position,
+            offset: self.offset,
+            wrap_offset: self.wrap_offset,
+            selection_anchor: None,
+            area: Rect::default(),
+        });
+        self.layout_windows();
+        self.focus_window(index);
+        Terminal::clear_screen();
+    }
+
+    fn close_window(&mut self) {
+        if self.windows.len() == 1 {
+            self.status_message = StatusMessage::from("Can't close the last window".to_string());
+            return;
+        }
+        let closing = self.current_window;
+        self.focus_window(if closing == 0 { 1 } else { closing.saturating_sub(1) });
+        self.windows.remove(closing);
+        self.layout.remove(closing);
+        if self.current_window > closing {
+            self.current_window = self.current_window.saturating_sub(1);
+        }
+        self.layout_windows();
+        Terminal::clear_screen();
+        self.scroll();
+    }
+
+    //focuses the window next to the cursor on the given side
+    fn move_focus(&mut self, code: KeyCode) {
+        let cursor = self.cursor_screen_position().unwrap_or(Position {
+            x: self.area.x,
+            y: self.area.y,
+        });
+        let area = self.area;
+        //the separator column sits between side by side windows
+        let target = match code {
+            Left => area.x.checked_sub(2).map(|x| (x, cursor.y)),
+            Right => Some((area.x.saturating_add(area.width).saturating_add(1), cursor.y)),
+            Up => area.y.checked_sub(1).map(|y| (cursor.x, y)),
+            Down => Some((cursor.x, area.y.saturating_add(area.height).saturating_add(1))),
+            _ => None,
+        };
+        if let Some(index) = target.and_then(|(x, y)| self.window_at(x, y)) {
+            self.focus_window(index);
+        }
+    }
+
+    fn resize_window(&mut self, kind: SplitKind, grow: bool) {
+        if self.layout.resize(self.current_window, kind, grow, RESIZE_STEP) {
+            self.layout_windows();
+            Terminal::clear_screen();
+            self.scroll();
+        }
+    }
+
+    //the screen column of the cursor, before horizontal scrolling
+    fn cursor_column(&self) -> usize {
         let Position {x, y} = self.cursor_position;
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
+        self.document.row(y).map_or(0, |row| row.column(x, self.config.tab_width))
+    }
+
+    //the digits of the longest line number and a space, or nothing without line numbers
+    fn gutter_width(&self) -> usize {
+        if self.config.line_numbers == LineNumbers::Off {
+            return 0;
+        }
+        let digits = self.document.len().max(1).to_string().len();
+        digits.saturating_add(1)
+    }
+
+    //the columns left for the text next to the gutter
+    fn text_width(&self) -> usize {
+        self.area.width.saturating_sub(self.gutter_width())
+    }
+
+    //where each screen line of a row starts, as grapheme index and column,
+    //rows only take more than one line with soft wrap
+    fn wrapped_lines(&self, y: usize) -> Vec<(usize, usize)> {
+        match self.document.row(y) {
+            Some(row) if self.config.soft_wrap => row.wrap(self.text_width(), self.config.tab_width),
+            _ => vec![(0, 0)],
+        }
+    }
+
+    //the screen line of its row the cursor is on and the column that line starts at
+    fn cursor_line(&self) -> (usize, usize) {
+        let lines = self.wrapped_lines(self.cursor_position.y);
+        let line = lines
+            .iter()
+            .rposition(|(start, _)| *start <= self.cursor_position.x)
+            .unwrap_or(0);
+        (line, lines.get(line).map_or(0, |(_, column)| *column))
+    }
+
+    //the screen lines from one row and line to another, if it is less than limit
+    fn lines_between(&self, from: (usize, usize), to: (usize, usize), limit: usize) -> Option<usize> {
+        if to < from {
+            return None;
+        }
+        let mut count = 0_usize;
+        for y in from.0..=to.0 {
+            count = count.saturating_add(if y == to.0 {
+                to.1.saturating_add(1)
+            } else {
+                self.wrapped_lines(y).len()
+            });
+            if y == from.0 {
+                count = count.saturating_sub(from.1);
+            }
+            if count > limit {
+                return None;
+            }
+        }
+        Some(count.saturating_sub(1))
+    }
+
+    //the cursor's place on screen, if it is in view
+    fn cursor_screen_position(&self) -> Option<Position> {
+        let column = self.cursor_column();
+        let height = self.area.height;
+        let (x, y) = if self.config.soft_wrap {
+            let (line, line_column) = self.cursor_line();
+            let y = self.lines_between(
+                (self.offset.y, self.wrap_offset),
+                (self.cursor_position.y, line),
+                height,
+            )?;
+            (column.saturating_sub(line_column), y)
+        } else {
+            let y = self.cursor_position.y.checked_sub(self.offset.y)?;
+            let x = column.checked_sub(self.offset.x)?;
+            if x >= self.text_width() {
+                return None;
+            }
+            (x, y)
+        };
+        (y < height).then(|| Position {
+            x: x.saturating_add(self.gutter_width()).saturating_add(self.area.x),
+            y: y.saturating_add(self.area.y),
+        })
+    }
+
+    //offset.x is a screen column, so wide graphemes and tabs scroll by their width
+    fn scroll(&mut self) {
+        if self.config.soft_wrap {
+            self.scroll_wrapped();
+            return;
+        }
+        let x = self.cursor_column();
+        let y = self.cursor_position.y;
+        let width = self.text_width();
+        let height = self.area.height;
         let offset = &mut self.offset;
         if y < offset.y {
             offset.y = y;
@@ -136,35 +626,148 @@ impl Editor {
         }
     }
 
-    fn draw_row(&self, row: &Row) {
-        let width = self.terminal.size().width as usize;
-        let start = self.offset.x;
-        let end = self.offset.x.saturating_add(width);
-        let row = row.render(start, end);
-        println!("{}\r", row)
+    //scrolls by screen lines instead of rows, nothing is scrolled horizontally
+    fn scroll_wrapped(&mut self) {
+        let height = self.area.height;
+        let cursor = (self.cursor_position.y, self.cursor_line().0);
+        let top = (self.offset.y, self.wrap_offset);
+        self.offset.x = 0;
+        if cursor < top {
+            (self.offset.y, self.wrap_offset) = cursor;
+        } else if self.lines_between(top, cursor, height).is_none() {
+            //puts the cursor on the last screen line
+            let (mut y, mut line) = cursor;
+            for _ in 1..height {
+                if line > 0 {
+                    line = line.saturating_sub(1);
+                } else if y > 0 {
+                    y = y.saturating_sub(1);
+                    line = self.wrapped_lines(y).len().saturating_sub(1);
+                } else {
+                    break;
+                }
+            }
+            (self.offset.y, self.wrap_offset) = (y, line);
+        }
+    }
+
+    //the row, first and last column of every line on screen, and whether
+    //it is the first line of its row
+    fn screen_lines(&self) -> Vec<(usize, usize, usize, bool)> {
+        let height = self.area.height;
+        let width = self.text_width();
+        if !self.config.soft_wrap {
+            return (0..height)
+                .map(|line| {
+                    let y = self.offset.y.saturating_add(line);
+                    (y, self.offset.x, self.offset.x.saturating_add(width), true)
+                })
+                .collect();
+        }
+        let mut result = Vec::with_capacity(height);
+        let mut y = self.offset.y;
+        let mut skip = self.wrap_offset;
+        while result.len() < height {
+            let lines = self.wrapped_lines(y);
+            for (line, (_, start)) in lines.iter().enumerate().skip(skip) {
+                let end = lines
+                    .get(line.saturating_add(1))
+                    .map_or(start.saturating_add(width), |(_, next)| *next);
+                result.push((y, *start, end, line == 0));
+            }
+            skip = 0;
+            y = y.saturating_add(1);
+        }
+        result.truncate(height);
+        result
+    }
+
+    //draws the columns start..end of the row
+    fn draw_row(&self, row: &Row, index: usize, start: usize, end: usize) -> Result<(), std::io::Error> {
+        //the selected part of the row is drawn with a different background
+        let (selection_start, selection_end) = match self.selection() {
+            Some((selection_start, selection_end))
+                if selection_start.y <= index && index <= selection_end.y =>
+            {
+                let from = if index == selection_start.y {
+                    row.column(selection_start.x, self.config.tab_width)
+                } else {
+                    0
+                };
+                let to = if index == selection_end.y {
+                    row.column(selection_end.x, self.config.tab_width)
+                } else {
+                    end
+                };
+                (from.clamp(start, end), to.clamp(start, end))
+            }
+            _ => (start, start),
+        };
+        self.draw_segment(row, start, selection_start)?;
+        Terminal::set_bg_color(self.config.selection_bg)?;
+        self.draw_segment(row, selection_start, selection_end.max(selection_start))?;
+        Terminal::reset_color()?;
+        self.draw_segment(row, selection_end.max(selection_start), end)?;
+        Terminal::reset_color()
+    }
+
+    fn draw_segment(&self, row: &Row, start: usize, end: usize) -> Result<(), std::io::Error> {
+        for (highlighting_type, text) in row.render(start, end, self.config.tab_width) {
+            Terminal::set_fg_color(highlighting_type.to_color())?;
+            print!("{text}");
+        }
+        Ok(())
     }
 
     #[allow(clippy::integer_division, clippy::arithmetic_side_effects)]
-    fn draw_rows(&self) {
-        let height = self.terminal.size().height;
-        for terminal_row in 0..height {
-            Terminal::clear_current_line();
-            if let Some(row) = self.document.row(
-                self.offset.y.saturating_add(terminal_row as usize)
-            ) {
-                self.draw_row(row);
+    fn draw_rows(&self) -> Result<(), std::io::Error> {
+        let Rect {x, y, width, height} = self.area;
+        let blank = " ".repeat(width);
+        for (terminal_row, (index, start, end, first)) in self.screen_lines().into_iter().enumerate() {
+            //only this window's part of the line is cleared
+            let position = Position {x, y: y + terminal_row};
+            Terminal::cursor_position(&position);
+            print!("{blank}");
+            Terminal::cursor_position(&position);
+            if let Some(row) = self.document.row(index) {
+                if first {
+                    self.draw_line_number(index)?;
+                } else {
+                    print!("{}", " ".repeat(self.gutter_width()));
+                }
+                self.draw_row(row, index, start, end)?;
             } else if self.document.is_empty() && 
                 terminal_row == height / 3 {
                     self.draw_welcome_message()
-            } else {
-                println!("\r")
             }
         }
+        Ok(())
+    }
+
+    fn draw_line_number(&self, index: usize) -> Result<(), std::io::Error> {
+        let width = self.gutter_width();
+        if width == 0 {
+            return Ok(());
+        }
+        let current = self.cursor_position.y;
+        let number = match self.config.line_numbers {
+            LineNumbers::Relative => index.abs_diff(current),
+            LineNumbers::Hybrid if index != current => index.abs_diff(current),
+            _ => index.saturating_add(1),
+        };
+        let color = if index == current {
+            self.config.current_line_number_fg
+        } else {
+            self.config.line_number_fg
+        };
+        Terminal::set_fg_color(color)?;
+        print!("{number:>digits$} ", digits = width.saturating_sub(1));
+        Terminal::reset_color()
     }
 
-    fn draw_status_bar(&self) -> Result<(), std::io::Error> {
+    fn draw_status_bar(&self, focused: bool) -> Result<(), std::io::Error> {
         let mut status;
-        let width = self.terminal.size().width as usize;
+        let width = self.area.width;
         let modified_indicator = if self.document.is_dirty() {
             " (modified)"
         } else {
@@ -175,61 +778,120 @@ impl Editor {
             filename = name.clone();
             //bad error handling
             filename = filename.split("\\").last().unwrap().to_string();
-            filename.truncate(20);
+            filename = fit_width(&filename, 20);
         } else {
             filename = "[No Name]".to_string();
         }
-        status = format!(" {} - {} lines{}",
+        //only shows which buffer this is when there is more than one
+        let buffer_indicator = if self.buffers.len() > 1 {
+            format!("[{}/{}] ", self.current_buffer.saturating_add(1), self.buffers.len())
+        } else {
+            String::new()
+        };
+        //the mode is only shown where the keys go
+        let mode_indicator = if self.config.vi_mode && focused {
+            format!("{}{} | ", self.vi.mode.name(), self.vi.pending())
+        } else {
+            String::new()
+        };
+        status = format!(" {}{}{} - {} lines{}",
+            mode_indicator,
+            buffer_indicator,
             filename,
             self.document.len(),
             modified_indicator,
         );
         
+        let final_newline_indicator = if self.document.has_final_newline() {
+            ""
+        } else {
+            " noeol"
+        };
         let line_indicator = format!(
-            "{}/{} ",
+            "{} | {}{} | {}/{} ",
+            self.document.file_type(),
+            self.document.line_ending_name(),
+            final_newline_indicator,
             self.cursor_position.y.saturating_add(1),
             self.document.len()
         );
 
-        let len = status.len() + line_indicator.len();
+        let len = text_width(&status).saturating_add(text_width(&line_indicator));
         status.push_str(&" ".repeat(width.saturating_sub(len)));
 
         status = format!("{}{}", status, line_indicator);
-        status.truncate(width);
+        status = fit_width(&status, width);
 
-        Terminal::set_fg_color(Color::Black)?;
-        Terminal::set_bg_color(Color::Green)?;
-        println!("{status}\r");
+        Terminal::cursor_position(&Position {
+            x: self.area.x,
+            y: self.area.y.saturating_add(self.area.height),
+        });
+        Terminal::set_fg_color(self.config.status_fg)?;
+        Terminal::set_bg_color(if focused {
+            self.config.status_bg
+        } else {
+            self.config.inactive_status_bg
+        })?;
+        print!("{status}");
         execute!(io::stdout(), style::ResetColor)?;
         Ok(())
     }
 
+    //draws over the bottom of the windows, which are redrawn once it is gone
+    fn draw_popup(&self) -> Result<(), std::io::Error> {
+        let bottom = self.terminal.size().height as usize;
+        let width = self.terminal.size().width as usize;
+        for (index, line) in self.popup.iter().enumerate() {
+            let Some(y) = bottom.checked_sub(index) else {
+                break;
+            };
+            Terminal::cursor_position(&Position {x: 0, y});
+            Terminal::clear_current_line();
+            let text: String = line.chars().take(width).collect();
+            if index == self.popup_selected {
+                Terminal::set_bg_color(self.config.selection_bg)?;
+                print!("{text:width$}");
+                Terminal::reset_color()?;
+            } else {
+                print!("{text}");
+            }
+        }
+        Ok(())
+    }
+
     fn draw_message_bar(&self) {
+        //the line below every window
+        Terminal::cursor_position(&Position {
+            x: 0,
+            y: (self.terminal.size().height as usize).saturating_add(1),
+        });
         Terminal::clear_current_line();
         let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
+        if message.time.elapsed() < self.config.message_timeout {
+            let text = fit_width(&message.text, self.terminal.size().width as usize);
             print!("{text}");
         }
     }
 
     fn draw_welcome_message(&self) {
         let mut welcome_message = format!("Kibi text editor -- version {VERSION}");
-        let width = self.terminal.size().width as usize;
+        let width = self.area.width;
         let len = welcome_message.len();
         #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
         let padding = width.saturating_sub(len) / 2;
         let spaces = " ".repeat(padding.saturating_sub(1));
         welcome_message = format!(" {}{}", spaces, welcome_message);
         welcome_message.truncate(width);
-        println!("{}\r", welcome_message);
+        print!("{}", welcome_message);
 
     }
 
     fn save(&mut self) {
         if self.document.filename.is_none() {
-            let new_name = self.prompt("Save as: ").unwrap_or(None);
+            let new_name = self
+                .prompt("Save as: ", |_, _, _| {})
+                .unwrap_or(None)
+                .filter(|name| !name.is_empty());
             if new_name.is_none() {
                 self.status_message = StatusMessage::from(
                     "Save aborted".to_string()
@@ -252,7 +914,12 @@ impl Editor {
 
     }
 
-    fn prompt(&mut self, message: &str) -> Result<Option<String>, std::io::Error> {
+    //the callback is run after every key press with the current input,
+    //returns None if the prompt was cancelled with Esc
+    fn prompt<C>(&mut self, message: &str, mut callback: C) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, &KeyEvent, &String),
+    {
         let mut result = String::new();
         loop {
             self.status_message = StatusMessage::from(
@@ -262,7 +929,13 @@ impl Editor {
             self.refresh_screen()?;
 
             let event = event::read()?;
-            if let Key(key_event) = event {
+            if let Resize(width, height) = event {
+                self.resize(width, height);
+            } else if let Paste(text) = event {
+                //the prompt is a single line, so only the first pasted line is kept
+                result.push_str(text.lines().next().unwrap_or_default());
+                callback(self, &KeyEvent::from(Null), &result);
+            } else if let Key(key_event) = event {
                 if let KeyEventKind::Press = key_event.kind {
                     match key_event {
                         KeyEvent{code: Enter, ..} => {
@@ -274,99 +947,779 @@ impl Editor {
                         }
 
                         KeyEvent {code: Backspace, ..} => {
-                            result.truncate(result.len().saturating_sub(1))
+                            result.pop();
                         }
 
                         KeyEvent{code: Esc, ..} => {
                             result.truncate(0);
-                            break;
+                            callback(self, &key_event, &result);
+                            self.status_message = StatusMessage::from(String::new());
+                            return Ok(None);
                         }
 
-                        _ => {
-                            continue;
-                        }
+                        _ => (),
                     }
+                    callback(self, &key_event, &result);
                 }
             } else {continue;}
         }
 
         self.status_message = StatusMessage::from(String::new());
-        if result.is_empty() {
-            return Ok(None)
-        }
         Ok(Some(result))
     }
 
-    fn delete_word(&mut self) {
-        let dist = self.jump_to_word_start();
-        for _i in 0..dist {
-            self.document.delete(&self.cursor_position);
+    fn search(&mut self) {
+        let old_position = self.cursor_position;
+        let old_offset = self.offset;
+        let mut direction = SearchDirection::Forward;
+        let query = self
+            .prompt(
+                "Search (ESC to cancel, arrows to navigate): ",
+                |editor, key, query| {
+                    let mut moved = false;
+                    match key.code {
+                        Right | Down => {
+                            direction = SearchDirection::Forward;
+                            editor.move_cursor(Right);
+                            moved = true;
+                        }
+                        Left | Up => direction = SearchDirection::Backward,
+                        _ => direction = SearchDirection::Forward,
+                    }
+                    if let Some(position) =
+                        editor.document.find(query, &editor.cursor_position, direction)
+                    {
+                        editor.cursor_position = position;
+                        editor.highlighted_match = Some((position, Position {
+                            x: position.x.saturating_add(query.graphemes(true).count()),
+                            y: position.y,
+                        }));
+                        editor.scroll();
+                    } else {
+                        editor.highlighted_match = None;
+                        if moved {
+                            editor.move_cursor(Left);
+                        }
+                    }
+                },
+            )
+            .unwrap_or(None);
+        self.highlighted_match = None;
+
+        if query.is_none() {
+            self.cursor_position = old_position;
+            self.offset = old_offset;
         }
     }
 
-    fn jump_to_next_word(&mut self) -> usize {
-        let Position {x, y} = self.cursor_position;
-        if self.document.row(y).is_some() {
-            let dist = self.document.row(y).unwrap().distance_to_end(x);
-            for _i in 0..dist {
-                self.move_cursor(Right);
+    //replaces regex matches from the cursor to the end of the document,
+    //asking for confirmation for every match until "all" is chosen
+    fn replace(&mut self) -> Result<(), std::io::Error> {
+        self.selection_anchor = None;
+        let pattern = self
+            .prompt("Replace regex (ESC to cancel): ", |_, _, _| {})?
+            .filter(|pattern| !pattern.is_empty());
+        let Some(pattern) = pattern else {
+            self.status_message = StatusMessage::from("Replace aborted".to_string());
+            return Ok(());
+        };
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(error) => {
+                self.status_message = StatusMessage::from(format!("ERR: Invalid regex: {error}"));
+                return Ok(());
+            }
+        };
+        let Some(replacement) = self.prompt("Replace with ($1 for groups): ", |_, _, _| {})? else {
+            self.status_message = StatusMessage::from("Replace aborted".to_string());
+            return Ok(());
+        };
+
+        let mut replace_all = false;
+        let mut count: usize = 0;
+        let mut position = self.cursor_position;
+        while let Some((start, end)) = self.document.find_regex(&regex, &position) {
+            self.cursor_position = start;
+            self.scroll();
+            let choice = if replace_all {
+                ReplaceChoice::All
+            } else {
+                self.highlighted_match = Some((start, end));
+                let choice = self.ask_replace();
+                self.highlighted_match = None;
+                choice?
+            };
+            match choice {
+                ReplaceChoice::Yes | ReplaceChoice::All => {
+                    replace_all = matches!(choice, ReplaceChoice::All);
+                    let text = self.document.expand_regex(&regex, &start, &replacement);
+                    position = self.document.replace(&start, &end, &text);
+                    count = count.saturating_add(1);
+                }
+                ReplaceChoice::No => position = end,
+                ReplaceChoice::Quit => break,
             }
-            return dist;
         }
-        return 0;
+
+        self.cursor_position = position;
+        self.scroll();
+        self.status_message = StatusMessage::from(format!("Replaced {count} occurrence(s)"));
+        Ok(())
     }
 
-    fn jump_to_word_start(&mut self) -> usize {
-        let Position {x, y} = self.cursor_position;
-        if self.document.row(y).is_some() {
-            let dist = self.document.row(y).unwrap().distance_to_start(x);
-            for _i in 0..dist {
-                self.move_cursor(Left);
+    fn ask_replace(&mut self) -> Result<ReplaceChoice, std::io::Error> {
+        loop {
+            match self.read_key("Replace match? (y)es, (n)o, (a)ll, (q)uit")? {
+                Char('y') | Enter => return Ok(ReplaceChoice::Yes),
+                Char('n') => return Ok(ReplaceChoice::No),
+                Char('a') => return Ok(ReplaceChoice::All),
+                Char('q') | Esc => return Ok(ReplaceChoice::Quit),
+                _ => (),
             }
-            return dist;
         }
-        return 0;
     }
 
-    fn move_cursor(&mut self, code: KeyCode) {
-        let terminal_height = self.terminal.size().height as usize;
-        let Position {mut x, mut y} = self.cursor_position;
-        let height = self.document.len();
-        let mut width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
-        };
-        
-        match code {
-            Up | Char('k') => y = y.saturating_sub(1),
-
-            Down | Char('j') => {
-                if y < height {
-                    y = y.saturating_add(1);
-                }
-            }
-            Left | Char('h') => {
-                if x > 0 {
-                    x -= 1
-                //goes up to previous line if cursor is at start of line
-                } else if y > 0 {
-                    y -=1;
-                    if let Some(row) = self.document.row(y) {
-                        x = row.len();
-                    } else {
-                        x = 0;
+    //shows the message and waits for a single key press
+    fn read_key(&mut self, message: &str) -> Result<KeyCode, std::io::Error> {
+        self.status_message = StatusMessage::from(message.to_string());
+        self.refresh_screen()?;
+        loop {
+            match event::read()? {
+                Key(key_event) => {
+                    if let KeyEventKind::Press = key_event.kind {
+                        self.status_message = StatusMessage::from(String::new());
+                        return Ok(key_event.code);
                     }
                 }
-            }
-
-            Right | Char('l') => {
-                if x < width {
-                    x += 1;
-                //goes to next line if cursor is at end of line
-                } else if y < height {
-                    x = 0;
-                    y += 1;
+                Resize(width, height) => {
+                    self.resize(width, height);
+                    self.refresh_screen()?;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn update_swap(&mut self) {
+        if self.document.update_swap().is_err() {
+            self.status_message = StatusMessage::from(
+                "ERR: Could not write swap file".to_string()
+            );
+        }
+        self.last_swap = Instant::now();
+    }
+
+    //offers to restore unsaved changes left behind by a crashed session
+    fn recover_swap(&mut self) -> Result<(), std::io::Error> {
+        let Some(recovered) = self.document.stale_swap() else {
+            return Ok(());
+        };
+        if recovered == self.document.file_contents() {
+            self.document.discard_stale_swap();
+            return Ok(());
+        }
+
+        loop {
+            let answer = self.read_key(
+                "Found unsaved changes from a previous session. (r)estore, (d)iff, (x) discard"
+            )?;
+            match answer {
+                Char('r') => {
+                    self.document.recover(&recovered);
+                    self.document.discard_stale_swap();
+                    self.status_message = StatusMessage::from(
+                        "Recovered unsaved changes".to_string()
+                    );
+                    return Ok(());
+                }
+                Char('d') => self.show_diff(&recovered)?,
+                Char('x') => {
+                    self.document.discard_stale_swap();
+                    self.status_message = StatusMessage::from(
+                        "Discarded unsaved changes".to_string()
+                    );
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    //temporarily shows the difference between the file and the recovered text
+    fn show_diff(&mut self, recovered: &str) -> Result<(), std::io::Error> {
+        //line endings are compared when recovering but not shown
+        let diff = diff::lines(
+            &self.document.file_contents().replace("\r\n", "\n"),
+            &recovered.replace("\r\n", "\n"),
+        )
+        .join("\n");
+        let mut diff_view = Buffer {
+            document: Document::from_text(&diff),
+            ..Buffer::default()
+        };
+
+        self.swap_view(&mut diff_view);
+        self.temporary_view = true;
+        let result = loop {
+            match self.read_key("Recovered changes, arrows to scroll, any other key to go back") {
+                Ok(code @ (Up | Down | PageUp | PageDown | Home | End)) => self.move_cursor(code),
+                Ok(_) => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        self.swap_view(&mut diff_view);
+        self.temporary_view = false;
+        result
+    }
+
+    //exchanges what is being edited with the buffer
+    fn swap_view(&mut self, buffer: &mut Buffer) {
+        mem::swap(&mut self.document, &mut buffer.document);
+        mem::swap(&mut self.cursor_position, &mut buffer.cursor_position);
+        mem::swap(&mut self.offset, &mut buffer.offset);
+        mem::swap(&mut self.wrap_offset, &mut buffer.wrap_offset);
+        mem::swap(&mut self.selection_anchor, &mut buffer.selection_anchor);
+    }
+
+    fn swap_buffer(&mut self, index: usize) {
+        if let Some(mut buffer) = self.buffers.get_mut(index).map(mem::take) {
+            self.swap_view(&mut buffer);
+            if let Some(slot) = self.buffers.get_mut(index) {
+                *slot = buffer;
+            }
+        }
+    }
+
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.leave_document();
+        //the swap file is only kept up to date for the buffer being edited
+        self.update_swap();
+        self.highlighted_match = None;
+        self.swap_buffer(self.current_buffer);
+        self.swap_buffer(index);
+        self.current_buffer = index;
+        self.clamp_view();
+        self.scroll();
+    }
+
+    fn open_buffer(&mut self, document: Document) {
+        self.buffers.push(Buffer {
+            document,
+            ..Buffer::default()
+        });
+        self.switch_buffer(self.buffers.len().saturating_sub(1));
+    }
+
+    fn buffer_document(&self, index: usize) -> Option<&Document> {
+        if index == self.current_buffer {
+            Some(&self.document)
+        } else {
+            self.buffers.get(index).map(|buffer| &buffer.document)
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn cycle_buffer(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        let index = if forward {
+            (self.current_buffer + 1) % len
+        } else {
+            (self.current_buffer + len - 1) % len
+        };
+        self.switch_buffer(index);
+    }
+
+    fn list_buffers(&mut self) -> Result<(), std::io::Error> {
+        let items: Vec<String> = (0..self.buffers.len())
+            .filter_map(|index| self.buffer_document(index))
+            .enumerate()
+            .map(|(index, document)| {
+                format!(
+                    "{} {}{}",
+                    index.saturating_add(1),
+                    document.filename.as_deref().unwrap_or("[No Name]"),
+                    if document.is_dirty() { " (modified)" } else { "" }
+                )
+            })
+            .collect();
+        if let Some(index) = self.pick("Buffers, Enter to switch, Esc to cancel", &items, self.current_buffer)? {
+            self.switch_buffer(index);
+        }
+        Ok(())
+    }
+
+    //shows the items as a list to choose from with the arrows and Enter
+    fn pick(&mut self, message: &str, items: &[String], selected: usize) -> Result<Option<usize>, std::io::Error> {
+        let mut list = Buffer {
+            document: Document::from_text(&items.join("\n")),
+            ..Buffer::default()
+        };
+        list.cursor_position.y = cmp::min(selected, items.len().saturating_sub(1));
+        let highlighted_match = self.highlighted_match.take();
+        self.swap_view(&mut list);
+        self.temporary_view = true;
+        let result = loop {
+            //the phantom line after the last item can't be chosen
+            let y = cmp::min(self.cursor_position.y, items.len().saturating_sub(1));
+            let len = self.document.row(y).map_or(0, Row::len);
+            self.cursor_position = Position {x: 0, y};
+            self.highlighted_match = Some((Position {x: 0, y}, Position {x: len, y}));
+            self.scroll();
+            match self.read_key(message) {
+                Ok(code @ (Up | Down | PageUp | PageDown | Home | End)) => self.move_cursor(code),
+                Ok(Enter) if !items.is_empty() => break Ok(Some(y)),
+                Ok(_) => break Ok(None),
+                Err(error) => break Err(error),
+            }
+        };
+        self.swap_view(&mut list);
+        self.temporary_view = false;
+        self.highlighted_match = highlighted_match;
+        result
+    }
+
+    //asks about every modified buffer before quitting, answering anything
+    //but yes or save keeps the editor open on that buffer
+    fn quit(&mut self) {
+        let others = (0..self.buffers.len()).filter(|index| *index != self.current_buffer);
+        let order: Vec<usize> = std::iter::once(self.current_buffer).chain(others).collect();
+        for index in order {
+            if !self.buffer_document(index).map_or(false, Document::is_dirty) {
+                continue;
+            }
+            self.switch_buffer(index);
+            let answer = self
+                .prompt("Are you sure you want to quit? Document has been modified. \'Yes\' to continue, \'Save\' to save and quit: ", |_, _, _| {})
+                .unwrap_or(None)
+                .unwrap_or_default();
+            if answer.trim().eq_ignore_ascii_case("save") {
+                self.save();
+                if self.document.is_dirty() {
+                    return;
+                }
+            } else if !answer.trim().eq_ignore_ascii_case("yes") {
+                return;
+            }
+        }
+        self.should_quit = true;
+    }
+
+    //fuzzy searches the files under the current directory and opens the
+    //chosen one in place of the current document
+    fn open_file(&mut self) -> Result<(), std::io::Error> {
+        let files = finder::files(Path::new("."));
+        let mut matches: Vec<usize> = (0..files.len()).collect();
+        let mut selected = 0;
+        self.show_matches(&files, &matches, selected);
+        let query = self.prompt(
+            "Open file (ESC to cancel, arrows to choose): ",
+            |editor, key, query| {
+                match key.code {
+                    //the best match is at the bottom, so up goes to worse ones
+                    Up => selected = cmp::min(selected.saturating_add(1), matches.len().saturating_sub(1)),
+                    Down => selected = selected.saturating_sub(1),
+                    _ => {
+                        matches = finder::rank(query, &files);
+                        selected = 0;
+                    }
+                }
+                editor.show_matches(&files, &matches, selected);
+            },
+        );
+        self.popup.clear();
+        let chosen = matches.get(selected).and_then(|index| files.get(*index));
+        match (query?, chosen) {
+            (Some(_), Some(path)) => self.open_path(&path.clone()),
+            (Some(_), None) => {
+                self.status_message = StatusMessage::from("No matching file".to_string());
+                Ok(())
+            }
+            (None, _) => Ok(()),
+        }
+    }
+
+    //keeps the selected match in view
+    fn show_matches(&mut self, files: &[String], matches: &[usize], selected: usize) {
+        let height = cmp::min(POPUP_HEIGHT, self.terminal.size().height as usize);
+        let first = selected.saturating_sub(height.saturating_sub(1));
+        self.popup = matches
+            .iter()
+            .skip(first)
+            .take(height)
+            .filter_map(|index| files.get(*index).cloned())
+            .collect();
+        self.popup_selected = selected.saturating_sub(first);
+    }
+
+    //the buffer editing the file, however its path was written
+    fn find_buffer(&self, path: &Path) -> Option<usize> {
+        let canonical = fs::canonicalize(path).ok()?;
+        (0..self.buffers.len()).find(|index| {
+            self.buffer_document(*index)
+                .and_then(|document| document.filename.as_deref())
+                .and_then(|filename| fs::canonicalize(filename).ok())
+                .map_or(false, |filename| filename == canonical)
+        })
+    }
+
+    fn open_path(&mut self, path: &str) -> Result<(), std::io::Error> {
+        //a file that is already open is switched to instead of being loaded twice
+        if let Some(index) = self.find_buffer(Path::new(path)) {
+            self.switch_buffer(index);
+            return Ok(());
+        }
+
+        if self.document.is_dirty() {
+            let answer = self
+                .prompt("Document has been modified. \'Yes\' to discard the changes, \'Save\' to save them first: ", |_, _, _| {})?
+                .unwrap_or_default();
+            if answer.trim().eq_ignore_ascii_case("save") {
+                self.save();
+                if self.document.is_dirty() {
+                    return Ok(());
+                }
+            } else if !answer.trim().eq_ignore_ascii_case("yes") {
+                self.status_message = StatusMessage::from("Open aborted".to_string());
+                return Ok(());
+            }
+        }
+
+        let Ok(mut document) = Document::open(path) else {
+            self.status_message = StatusMessage::from(format!("ERR: Could not open file: {path}"));
+            return Ok(());
+        };
+        document.set_backup(self.document.backup());
+        self.document.remove_swap();
+        self.replace_document(document);
+        self.recover_swap()?;
+        self.scroll();
+        Ok(())
+    }
+
+    fn replace_document(&mut self, document: Document) {
+        self.leave_document();
+        self.document = document;
+        self.cursor_position = Position::default();
+        self.offset = Position::default();
+        self.wrap_offset = 0;
+        self.selection_anchor = None;
+        self.highlighted_match = None;
+    }
+
+    //opens the file in a buffer of its own, except that the empty
+    //document the editor starts with is replaced
+    fn open_in_buffer(&mut self, path: &Path) -> Result<(), std::io::Error> {
+        if let Some(index) = self.find_buffer(path) {
+            self.switch_buffer(index);
+            return Ok(());
+        }
+        let filename = path.to_string_lossy();
+        let Ok(mut document) = Document::open(&filename) else {
+            self.status_message = StatusMessage::from(format!("ERR: Could not open file: {filename}"));
+            return Ok(());
+        };
+        document.set_backup(self.document.backup());
+        let untouched = self.document.filename.is_none()
+            && self.document.is_empty()
+            && !self.document.is_dirty();
+        if untouched {
+            self.replace_document(document);
+        } else {
+            self.open_buffer(document);
+        }
+        self.recover_swap()?;
+        self.scroll();
+        Ok(())
+    }
+
+    //shows the directory as a tree in the focused window until a file is
+    //opened or the browser is closed
+    fn browse(&mut self, root: PathBuf) -> Result<(), std::io::Error> {
+        let mut tree = Tree::new(root);
+        let mut entries = tree.entries();
+        let mut view = Buffer {
+            document: tree_document(&tree, &entries),
+            ..Buffer::default()
+        };
+        let highlighted_match = self.highlighted_match.take();
+        self.swap_view(&mut view);
+        self.temporary_view = true;
+        let mut message = BROWSER_HELP.to_string();
+        let result = loop {
+            let y = cmp::min(self.cursor_position.y, entries.len().saturating_sub(1));
+            let len = self.document.row(y).map_or(0, Row::len);
+            self.cursor_position = Position {x: 0, y};
+            self.highlighted_match = Some((Position {x: 0, y}, Position {x: len, y}));
+            self.scroll();
+            let code = match self.read_key(&message) {
+                Ok(code) => code,
+                Err(error) => break Err(error),
+            };
+            message = BROWSER_HELP.to_string();
+            let mut select = None;
+            match (code, entries.get(y)) {
+                (Up | Down | PageUp | PageDown | Home | End, _) => self.move_cursor(code),
+                (Esc | Char('q'), _) => break Ok(None),
+                (Enter | Right, Some(entry)) if entry.is_dir => tree.expand(&entry.path),
+                (Enter, Some(entry)) => break Ok(Some(entry.path.clone())),
+                (Left, Some(entry)) if entry.is_dir && tree.is_expanded(&entry.path) => {
+                    tree.collapse(&entry.path);
+                }
+                //goes to the directory the entry is in
+                (Left, Some(entry)) => select = entry.path.parent().map(Path::to_path_buf),
+                (Char('n'), entry) => {
+                    let dir = match entry {
+                        Some(entry) if entry.is_dir => entry.path.clone(),
+                        Some(entry) => entry.path.parent().map_or_else(|| tree.root().to_path_buf(), Path::to_path_buf),
+                        None => tree.root().to_path_buf(),
+                    };
+                    match self.create_entry(&dir) {
+                        Ok(Some(path)) => {
+                            if let Some(parent) = path.parent() {
+                                tree.expand(parent);
+                            }
+                            select = Some(path);
+                        }
+                        Ok(None) => (),
+                        Err(error) => message = format!("ERR: {error}"),
+                    }
+                }
+                (Char('r'), Some(entry)) => match self.rename_entry(entry, &mut view.document) {
+                    Ok(path) => select = path,
+                    Err(error) => message = format!("ERR: {error}"),
+                },
+                (Char('d'), Some(entry)) => match self.delete_entry(entry, &mut view.document) {
+                    Ok(true) => {
+                        message = format!("{} is still open, save it to keep its text", entry.path.display());
+                    }
+                    Ok(false) => (),
+                    Err(error) => message = format!("ERR: {error}"),
+                },
+                _ => (),
+            }
+            entries = tree.entries();
+            self.document = tree_document(&tree, &entries);
+            if let Some(index) = select.and_then(|path| entries.iter().position(|entry| entry.path == path)) {
+                self.cursor_position.y = index;
+            }
+        };
+        self.swap_view(&mut view);
+        self.temporary_view = false;
+        self.highlighted_match = highlighted_match;
+        match result? {
+            Some(path) => self.open_in_buffer(&path),
+            None => Ok(()),
+        }
+    }
+
+    //a name ending in `/` creates a directory
+    fn create_entry(&mut self, dir: &Path) -> Result<Option<PathBuf>, std::io::Error> {
+        let name = self
+            .prompt("New file (end with / for a directory): ", |_, _, _| {})?
+            .unwrap_or_default();
+        if name.trim().is_empty() {
+            return Ok(None);
+        }
+        let path = dir.join(relative_name(name.trim())?);
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+        }
+        //`dir.join` keeps the trailing slash, which entries don't have
+        Ok(Some(path.components().collect()))
+    }
+
+    //renames within the same directory, open documents follow their file;
+    //`current` is the document the browser is shown in place of
+    fn rename_entry(&mut self, entry: &Entry, current: &mut Document) -> Result<Option<PathBuf>, std::io::Error> {
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let new_name = self
+            .prompt(&format!("Rename {name} to: "), |_, _, _| {})?
+            .unwrap_or_default();
+        if new_name.trim().is_empty() || new_name.trim() == name {
+            return Ok(None);
+        }
+        let dir = entry.path.parent().unwrap_or_else(|| Path::new(""));
+        let target = dir.join(relative_name(new_name.trim())?);
+        if target.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+        }
+        //the old paths can only be resolved before the rename
+        let old = fs::canonicalize(&entry.path)?;
+        let moved: Vec<(&mut Document, PathBuf)> = open_documents(&mut self.buffers, current)
+            .filter_map(|document| {
+                let filename = fs::canonicalize(document.filename.as_deref()?).ok()?;
+                let rest = filename.strip_prefix(&old).ok()?.to_path_buf();
+                Some((document, rest))
+            })
+            .collect();
+        fs::rename(&entry.path, &target)?;
+        for (document, rest) in moved {
+            let path = if rest.as_os_str().is_empty() { target.clone() } else { target.join(rest) };
+            document.filename = Some(path.to_string_lossy().into_owned());
+        }
+        Ok(Some(target))
+    }
+
+    //only empty directories are deleted, so nothing is lost by accident;
+    //documents open on a deleted file are marked modified, so their text
+    //isn't dropped without asking, returns whether there were any
+    fn delete_entry(&mut self, entry: &Entry, current: &mut Document) -> Result<bool, std::io::Error> {
+        let answer = self.read_key(&format!("Delete {}? (y/n)", entry.path.display()))?;
+        if answer != Char('y') {
+            return Ok(false);
+        }
+        if entry.is_dir {
+            fs::remove_dir(&entry.path)?;
+            return Ok(false);
+        }
+        //the path can only be resolved before the file is gone
+        let deleted = fs::canonicalize(&entry.path)?;
+        let open: Vec<&mut Document> = open_documents(&mut self.buffers, current)
+            .filter(|document| {
+                document
+                    .filename
+                    .as_deref()
+                    .and_then(|filename| fs::canonicalize(filename).ok())
+                    .map_or(false, |filename| filename == deleted)
+            })
+            .collect();
+        fs::remove_file(&entry.path)?;
+        let was_open = !open.is_empty();
+        for document in open {
+            document.mark_dirty();
+        }
+        Ok(was_open)
+    }
+
+    fn convert_line_endings(&mut self) {
+        let answer = self
+            .prompt("Convert line endings to (LF/CRLF): ", |_, _, _| {})
+            .unwrap_or(None)
+            .unwrap_or_default();
+        let line_ending = match answer.trim().to_ascii_lowercase().as_str() {
+            "lf" => LineEnding::Lf,
+            "crlf" => LineEnding::CrLf,
+            _ => {
+                self.status_message = StatusMessage::from(
+                    "Conversion aborted".to_string()
+                );
+                return;
+            }
+        };
+        self.document.set_line_ending(line_ending);
+        self.status_message = StatusMessage::from(
+            format!("Line endings converted to {}", line_ending.name())
+        );
+    }
+
+    fn go_to_line(&mut self) {
+        let answer = self
+            .prompt("Go to (line, line:column, +N, -N or N%): ", |_, _, _| {})
+            .unwrap_or(None);
+        let Some(answer) = answer else {
+            return;
+        };
+        match parse_location(answer.trim(), self.cursor_position.y, self.document.len()) {
+            Some((line, column)) => self.go_to(line, column),
+            None => {
+                self.status_message =
+                    StatusMessage::from(format!("ERR: Invalid location `{}`", answer.trim()));
+            }
+        }
+    }
+
+    //moves the cursor to a row and grapheme, both counted from 0 and clamped to the document
+    fn go_to(&mut self, line: usize, column: usize) {
+        let y = cmp::min(line, self.document.len().saturating_sub(1));
+        let x = self.document.row(y).map_or(0, |row| cmp::min(column, row.len()));
+        self.selection_anchor = None;
+        self.cursor_position = Position {x, y};
+        self.scroll();
+    }
+
+    fn delete_word(&mut self) {
+        let dist = self.jump_to_word_start();
+        for _i in 0..dist {
+            self.document.delete(&self.cursor_position);
+        }
+    }
+
+    fn jump_to_next_word(&mut self) -> usize {
+        let Position {x, y} = self.cursor_position;
+        if self.document.row(y).is_some() {
+            let dist = self.document.row(y).unwrap().distance_to_end(x);
+            for _i in 0..dist {
+                self.move_cursor(Right);
+            }
+            return dist;
+        }
+        return 0;
+    }
+
+    fn jump_to_word_start(&mut self) -> usize {
+        let Position {x, y} = self.cursor_position;
+        if self.document.row(y).is_some() {
+            let dist = self.document.row(y).unwrap().distance_to_start(x);
+            for _i in 0..dist {
+                self.move_cursor(Left);
+            }
+            return dist;
+        }
+        return 0;
+    }
+
+    fn move_cursor(&mut self, code: KeyCode) {
+        if self.config.soft_wrap && matches!(code, Up | Down | Char('k' | 'j')) {
+            self.move_line(matches!(code, Down | Char('j')));
+            self.scroll();
+            return;
+        }
+        let terminal_height = self.area.height;
+        let Position {mut x, mut y} = self.cursor_position;
+        let height = self.document.len();
+        let mut width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+        
+        match code {
+            Up | Char('k') => y = y.saturating_sub(1),
+
+            Down | Char('j') => {
+                if y < height {
+                    y = y.saturating_add(1);
+                }
+            }
+            Left | Char('h') => {
+                if x > 0 {
+                    x -= 1
+                //goes up to previous line if cursor is at start of line
+                } else if y > 0 {
+                    y -=1;
+                    if let Some(row) = self.document.row(y) {
+                        x = row.len();
+                    } else {
+                        x = 0;
+                    }
+                }
+            }
+
+            Right | Char('l') => {
+                if x < width {
+                    x += 1;
+                //goes to next line if cursor is at end of line
+                } else if y < height {
+                    x = 0;
+                    y += 1;
                 }
             }
 
@@ -408,77 +1761,869 @@ impl Editor {
         self.scroll()
     }
 
-    pub fn handle_key_press(&mut self, key_event: &KeyEvent) -> Result<(), std::io::Error> {
-        match  key_event {
-            KeyEvent {modifiers: KeyModifiers::CONTROL, code: Char('q'), ..} => {
-                if self.document.is_dirty() {
-                    let result = self.prompt("Are you sure you want to quit? Document has been modified. \'Yes\' to continue, \'Save\' to save and quit: ").unwrap_or(None);
-                    if result.is_some() {
-                        let answer = result.unwrap();
-                        if answer.trim().eq_ignore_ascii_case("save") {
-                            self.save();
-                            self.should_quit = true;
-                        } else {
-                            self.should_quit = answer.trim().eq_ignore_ascii_case("yes")
-                        }
-                    };
+    //moves to the screen line above or below, keeping the column within the line
+    fn move_line(&mut self, down: bool) {
+        let y = self.cursor_position.y;
+        let (line, line_column) = self.cursor_line();
+        let column = self.cursor_column().saturating_sub(line_column);
+        let (y, line) = if down {
+            if line.saturating_add(1) < self.wrapped_lines(y).len() {
+                (y, line.saturating_add(1))
+            } else if y < self.document.len() {
+                (y.saturating_add(1), 0)
+            } else {
+                return;
+            }
+        } else if line > 0 {
+            (y, line.saturating_sub(1))
+        } else if y > 0 {
+            let y = y.saturating_sub(1);
+            (y, self.wrapped_lines(y).len().saturating_sub(1))
+        } else {
+            return;
+        };
+        let lines = self.wrapped_lines(y);
+        let (start, start_column) = lines.get(line).copied().unwrap_or_default();
+        let mut x = self.document.row(y).map_or(0, |row| {
+            row.index_at_column(start_column.saturating_add(column), self.config.tab_width)
+        });
+        //stays on this line instead of moving to the start of the next one
+        if let Some((next, _)) = lines.get(line.saturating_add(1)) {
+            x = cmp::min(x, next.saturating_sub(1));
+        }
+        self.cursor_position = Position {x: x.max(start), y};
+    }
+
+    //the selected range in document order, if anything is selected
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+        //vi's visual modes include the character under the cursor, or whole lines
+        if self.config.vi_mode && self.vi.mode.is_visual() {
+            let (start, end) = if anchor < cursor { (anchor, cursor) } else { (cursor, anchor) };
+            return Some(if self.vi.mode == Mode::VisualLine {
+                let len = self.document.row(end.y).map_or(0, Row::len);
+                (Position {x: 0, y: start.y}, Position {x: len, y: end.y})
+            } else {
+                (start, vi::next(&self.document, end).unwrap_or(end))
+            });
+        }
+        match anchor.cmp(&cursor) {
+            cmp::Ordering::Less => Some((anchor, cursor)),
+            cmp::Ordering::Greater => Some((cursor, anchor)),
+            cmp::Ordering::Equal => None,
+        }
+    }
+
+    fn extend_selection(&mut self, movement: Command) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.cursor_position);
+        }
+        self.move_by(movement);
+    }
+
+    fn move_by(&mut self, movement: Command) {
+        match movement {
+            Command::WordLeft => _ = self.jump_to_word_start(),
+            Command::WordRight => _ = self.jump_to_next_word(),
+            Command::MoveLeft => self.move_cursor(Left),
+            Command::MoveRight => self.move_cursor(Right),
+            Command::MoveUp => self.move_cursor(Up),
+            Command::MoveDown => self.move_cursor(Down),
+            Command::PageUp => self.move_cursor(PageUp),
+            Command::PageDown => self.move_cursor(PageDown),
+            Command::LineStart => self.move_cursor(Home),
+            Command::LineEnd => self.move_cursor(End),
+            _ => (),
+        }
+    }
+
+    //removes the selected text, returns false if nothing was selected
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.selection_anchor = None;
+        if let Some((start, end)) = selection {
+            self.document.delete_range(&start, &end);
+            self.cursor_position = start;
+            self.scroll();
+            return true;
+        }
+        false
+    }
+
+    fn copy(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            let text = self.document.text(&start, &end);
+            self.copy_to_clipboard(&text);
+        }
+    }
+
+    fn cut(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            let text = self.document.text(&start, &end);
+            self.copy_to_clipboard(&text);
+            self.delete_selection();
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) {
+        self.status_message = if self.clipboard.copy(text).is_ok() {
+            StatusMessage::from("Copied selection".to_string())
+        } else {
+            StatusMessage::from("ERR: Clipboard unavailable, copied internally".to_string())
+        };
+    }
+
+    fn paste(&mut self) {
+        self.delete_selection();
+        let text = self.clipboard.paste();
+        if !text.is_empty() {
+            self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+            self.scroll();
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse_event: &MouseEvent) {
+        let MouseEvent {kind, column, row, modifiers} = *mouse_event;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                //a click focuses the window it lands in
+                let Some(window) = self.window_at(column as usize, row as usize) else {
+                    return;
+                };
+                self.focus_window(window);
+                let Some((column, row)) = self.area_cell(column, row) else {
+                    return;
+                };
+                let position = self.position_at(column, row);
+                let double_click = self.last_click.map_or(false, |(time, last)| {
+                    last == position && time.elapsed() <= DOUBLE_CLICK_INTERVAL
+                });
+                if double_click {
+                    self.last_click = None;
+                    self.select_word(position);
+                } else {
+                    self.last_click = Some((Instant::now(), position));
+                    //shift+click extends the selection from the cursor
+                    if !modifiers.contains(KeyModifiers::SHIFT) {
+                        self.selection_anchor = None;
+                    } else if self.selection_anchor.is_none() {
+                        self.selection_anchor = Some(self.cursor_position);
+                    }
+                    self.cursor_position = position;
+                }
+                self.scroll();
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                //the cursor was placed where the button went down
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position);
+                }
+                //dragging past the window keeps selecting in it
+                let column = (column as usize).saturating_sub(self.area.x);
+                let row = (row as usize).saturating_sub(self.area.y);
+                self.cursor_position = self.position_at(column, row);
+                self.scroll();
+            }
+            //scrolling only moves the view under the pointer, the cursor stays where it is
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let Some(window) = self.window_at(column as usize, row as usize) else {
+                    return;
+                };
+                let focused = self.current_window;
+                self.enter_window(window);
+                self.offset.y = if kind == MouseEventKind::ScrollUp {
+                    self.offset.y.saturating_sub(SCROLL_LINES)
+                } else {
+                    cmp::min(
+                        self.offset.y.saturating_add(SCROLL_LINES),
+                        self.document.len().saturating_sub(1),
+                    )
+                };
+                self.wrap_offset = 0;
+                self.enter_window(focused);
+            }
+            _ => (),
+        }
+    }
+
+    //the cell relative to the focused window's text, if it is inside it
+    fn area_cell(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        let (column, row) = (column as usize, row as usize);
+        self.area
+            .contains(column, row)
+            .then(|| (column.saturating_sub(self.area.x), row.saturating_sub(self.area.y)))
+    }
+
+    //the document position shown at a screen cell, clamped to the text
+    fn position_at(&self, column: usize, row: usize) -> Position {
+        let lines = self.screen_lines();
+        let Some(&(y, start, end, _)) = lines.get(row).or_else(|| lines.last()) else {
+            return self.cursor_position;
+        };
+        let y = cmp::min(y, self.document.len());
+        let x = self
+            .document
+            .row(y)
+            .map_or(0, |row| {
+                //clicks on the gutter land at the start of the line,
+                //clicks past a wrapped line on its last grapheme
+                let column = column.saturating_sub(self.gutter_width());
+                let column = cmp::min(start.saturating_add(column), end.saturating_sub(1));
+                row.index_at_column(column.max(start), self.config.tab_width)
+            });
+        Position {x, y}
+    }
+
+    fn select_word(&mut self, position: Position) {
+        if let Some(row) = self.document.row(position.y) {
+            let (start, end) = row.word_at(position.x);
+            self.selection_anchor = Some(Position {x: start, y: position.y});
+            self.cursor_position = Position {x: end, y: position.y};
+        }
+    }
+
+    //bracketed paste delivers the whole text at once, so it is inserted
+    //as is instead of going through the key handlers
+    fn handle_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if self.config.vi_mode && self.vi.mode != Mode::Insert {
+            self.vi_paste_text(text);
+            return;
+        }
+        self.document.begin_transaction(&self.cursor_position);
+        self.delete_selection();
+        if !text.is_empty() {
+            self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+        }
+        self.document.end_transaction(&self.cursor_position);
+        self.scroll();
+    }
+
+    fn undo(&mut self) {
+        //what an open transaction did so far, like typing in vi's insert
+        //mode, is undone on its own and later edits start from where undo left
+        self.document.checkpoint(&self.cursor_position);
+        if let Some(position) = self.document.undo() {
+            self.cursor_position = position;
+            self.document.checkpoint(&self.cursor_position);
+            self.scroll();
+        } else {
+            self.status_message = StatusMessage::from(
+                "Nothing to undo".to_string()
+            );
+        }
+    }
+
+    fn redo(&mut self) {
+        self.document.checkpoint(&self.cursor_position);
+        if let Some(position) = self.document.redo() {
+            self.cursor_position = position;
+            self.document.checkpoint(&self.cursor_position);
+            self.scroll();
+        } else {
+            self.status_message = StatusMessage::from(
+                "Nothing to redo".to_string()
+            );
+        }
+    }
+
+    //plain characters typed in normal and visual mode are vi commands, the
+    //arrows are motions and everything else goes through the keymap; returns
+    //whether the key was used
+    fn handle_vi_key(&mut self, key_event: &KeyEvent) -> Result<bool, std::io::Error> {
+        if self.vi.mode == Mode::Insert {
+            if key_event.code != Esc {
+                //only what edits the text is replayed by `.`, not commands like save
+                let edits = matches!(key_event.code, Char(_) | Enter | Tab | Backspace | Delete)
+                    && !key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+                if let Some(keys) = self.vi.recording.as_mut().filter(|_| edits) {
+                    keys.push(*key_event);
+                }
+                return Ok(false);
+            }
+            self.end_vi_insert();
+            return Ok(true);
+        }
+
+        let modifiers = key_event.modifiers.difference(KeyModifiers::SHIFT);
+        let c = match key_event.code {
+            Char(c) if modifiers.is_empty() => c,
+            //vim's redo, replace stays available in insert mode
+            Char('r') if modifiers == KeyModifiers::CONTROL && self.vi.pending().is_empty() => {
+                self.selection_anchor = None;
+                self.vi.mode = Mode::Normal;
+                self.redo();
+                self.clamp_vi_cursor();
+                return Ok(true);
+            }
+            Esc => {
+                self.vi.cancel();
+                if self.vi.mode.is_visual() {
+                    self.vi.mode = Mode::Normal;
+                    self.selection_anchor = None;
+                }
+                return Ok(true);
+            }
+            //tab isn't a vi command, so it doesn't indent outside of insert mode
+            Tab | BackTab => return Ok(true),
+            _ if !modifiers.is_empty() => return Ok(false),
+            Left | Backspace => 'h',
+            Right => 'l',
+            Up => 'k',
+            Down | Enter => 'j',
+            Home => '0',
+            End => '$',
+            _ => return Ok(false),
+        };
+        self.vi.keys.push(*key_event);
+        if let Parsed::Done {action, count} = self.vi.feed(c) {
+            let keys = mem::take(&mut self.vi.keys);
+            self.run_vi_action(action, count, keys)?;
+            if self.vi.mode != Mode::Insert {
+                self.clamp_vi_cursor();
+            }
+        }
+        Ok(true)
+    }
+
+    //leaves insert mode like Esc, ending the transaction vi_insert began
+    fn end_vi_insert(&mut self) {
+        if let Some(mut keys) = self.vi.recording.take() {
+            keys.push(KeyEvent::from(Esc));
+            self.vi.last_change = keys;
+        }
+        self.vi.mode = Mode::Normal;
+        self.document.end_transaction(&self.cursor_position);
+        self.cursor_position.x = self.cursor_position.x.saturating_sub(1);
+        self.clamp_vi_cursor();
+    }
+
+    //what the document being switched away from is in the middle of is
+    //finished: vi goes back to normal mode and open transactions are closed,
+    //so they don't swallow later edits of this document or end on another one
+    fn leave_document(&mut self) {
+        if self.config.vi_mode {
+            if self.vi.mode == Mode::Insert {
+                self.end_vi_insert();
+            } else if self.vi.mode.is_visual() {
+                self.vi.mode = Mode::Normal;
+                self.selection_anchor = None;
+            }
+            self.vi.cancel();
+        }
+        self.document.close_transactions(&self.cursor_position);
+    }
+
+    //outside of insert mode the cursor stays on a character, like in vim
+    fn clamp_vi_cursor(&mut self) {
+        let y = cmp::min(self.cursor_position.y, self.document.len().saturating_sub(1));
+        let len = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = Position {x: cmp::min(self.cursor_position.x, len.saturating_sub(1)), y};
+    }
+
+    fn run_vi_action(&mut self, action: Action, count: Option<usize>, keys: Vec<KeyEvent>) -> Result<(), std::io::Error> {
+        let cursor = self.cursor_position;
+        match action {
+            Action::Move(motion) => {
+                if let Some(position) = vi::target(&self.document, cursor, motion, count) {
+                    self.cursor_position = position;
+                }
+            }
+            Action::Operate(operator, motion) => {
+                //cw changes to the end of the word like ce, leaving the space after it
+                let on_word = self
+                    .document
+                    .row(cursor.y)
+                    .map_or(false, |row| !row.slice(cursor.x, cursor.x.saturating_add(1)).trim().is_empty());
+                let range = if operator == Operator::Change && motion == Motion::WordForward && on_word {
+                    Some(vi::change_word_range(&self.document, cursor, count))
+                } else {
+                    vi::range(&self.document, cursor, motion, count)
+                };
+                if let Some(range) = range {
+                    self.vi_operate(operator, range, keys);
+                }
+            }
+            Action::OperateSelection(operator) => {
+                let range = match (self.selection(), self.vi.mode) {
+                    (Some((start, end)), Mode::VisualLine) => Range::Lines(start.y, end.y),
+                    (Some((start, end)), _) => Range::Chars(start, end),
+                    (None, _) => return Ok(()),
+                };
+                self.selection_anchor = None;
+                self.vi.mode = Mode::Normal;
+                //visual changes aren't repeated, their size depends on the selection
+                self.vi_operate(operator, range, Vec::new());
+            }
+            Action::Insert(insert) => {
+                self.vi_insert(insert);
+                self.vi.recording = Some(keys);
+            }
+            Action::Paste {before} => {
+                self.vi_paste(before, count.unwrap_or(1));
+                self.vi.last_change = keys;
+            }
+            Action::Undo => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.undo();
+                }
+            }
+            Action::Repeat => self.repeat_change(count)?,
+            Action::Visual(mode) => {
+                if self.vi.mode == mode {
+                    self.vi.mode = Mode::Normal;
+                    self.selection_anchor = None;
                 } else {
-                    self.should_quit = true;
+                    self.vi.mode = mode;
+                    self.selection_anchor = Some(self.selection_anchor.unwrap_or(cursor));
+                }
+            }
+            Action::SwapAnchor => {
+                if let Some(anchor) = self.selection_anchor {
+                    self.selection_anchor = Some(cursor);
+                    self.cursor_position = anchor;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //deleted and yanked text goes to vi's register, whole lines are
+    //remembered as such so they are pasted as lines
+    fn vi_operate(&mut self, operator: Operator, range: Range, keys: Vec<KeyEvent>) {
+        //a change is undone as one step with what is typed after it
+        if operator == Operator::Change {
+            self.document.begin_transaction(&self.cursor_position);
+        }
+        let line_end = |document: &Document, y: usize| Position {x: document.row(y).map_or(0, Row::len), y};
+        match range {
+            Range::Chars(start, end) => {
+                //nothing to take, like `x` on an empty line, keeps the register as it is
+                if start != end {
+                    self.vi.register = self.document.text(&start, &end);
+                    self.vi.linewise = false;
+                }
+                if operator != Operator::Yank {
+                    self.document.delete_range(&start, &end);
+                }
+                self.cursor_position = start;
+            }
+            Range::Lines(first, last) => {
+                let start = Position {x: 0, y: first};
+                let end = line_end(&self.document, last);
+                self.vi.register = format!("{}\n", self.document.text(&start, &end));
+                self.vi.linewise = true;
+                match operator {
+                    Operator::Yank => self.cursor_position.y = first,
+                    //the lines are emptied, keeping one to type into
+                    Operator::Change => {
+                        self.document.delete_range(&start, &end);
+                        self.cursor_position = start;
+                    }
+                    Operator::Delete => {
+                        let y = if last.saturating_add(1) < self.document.len() {
+                            self.document.delete_range(&start, &Position {x: 0, y: last.saturating_add(1)});
+                            first
+                        } else if first > 0 {
+                            //the last lines take the line break before them along
+                            let before = line_end(&self.document, first.saturating_sub(1));
+                            self.document.delete_range(&before, &end);
+                            first.saturating_sub(1)
+                        } else {
+                            self.document.delete_range(&start, &end);
+                            0
+                        };
+                        self.cursor_position = Position {x: vi::first_non_blank(&self.document, y), y};
+                    }
+                }
+            }
+        }
+        match operator {
+            Operator::Yank => (),
+            Operator::Delete => self.vi.last_change = keys,
+            Operator::Change => {
+                self.vi.mode = Mode::Insert;
+                self.vi.recording = Some(keys);
+            }
+        }
+    }
+
+    //everything up to leaving insert mode is undone as one step
+    fn vi_insert(&mut self, insert: vi::Insert) {
+        self.document.begin_transaction(&self.cursor_position);
+        let Position {x, y} = self.cursor_position;
+        let len = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = match insert {
+            vi::Insert::Before => self.cursor_position,
+            vi::Insert::After => Position {x: cmp::min(x.saturating_add(1), len), y},
+            vi::Insert::LineStart => Position {x: vi::first_non_blank(&self.document, y), y},
+            vi::Insert::LineEnd => Position {x: len, y},
+            vi::Insert::LineBelow => {
+                self.document.insert_str(&Position {x: len, y}, "\n");
+                Position {x: 0, y: y.saturating_add(1)}
+            }
+            vi::Insert::LineAbove => {
+                self.document.insert_str(&Position {x: 0, y}, "\n");
+                Position {x: 0, y}
+            }
+        };
+        self.vi.mode = Mode::Insert;
+    }
+
+    //lines go below or above the cursor's line, other text after or at the cursor
+    fn vi_paste(&mut self, before: bool, count: usize) {
+        if self.vi.register.is_empty() {
+            return;
+        }
+        let text = self.vi.register.repeat(count);
+        let Position {x, y} = self.cursor_position;
+        let len = self.document.row(y).map_or(0, Row::len);
+        if self.vi.linewise {
+            let y = if before {
+                self.document.insert_str(&Position {x: 0, y}, &text);
+                y
+            } else if y.saturating_add(1) < self.document.len() {
+                self.document.insert_str(&Position {x: 0, y: y.saturating_add(1)}, &text);
+                y.saturating_add(1)
+            } else {
+                //there is no line after the last one to insert before
+                let lines = text.strip_suffix('\n').unwrap_or(&text);
+                self.document.insert_str(&Position {x: len, y}, &format!("\n{lines}"));
+                y.saturating_add(1)
+            };
+            self.cursor_position = Position {x: vi::first_non_blank(&self.document, y), y};
+        } else {
+            let at = if before { x } else { cmp::min(x.saturating_add(1), len) };
+            let end = self.document.insert_str(&Position {x: at, y}, &text);
+            //the cursor ends up on the last pasted character
+            self.cursor_position = Position {x: end.x.saturating_sub(1), y: end.y};
+        }
+    }
+
+    //pasting outside of insert mode puts the text after the cursor like `p`,
+    //without typing it as commands or replacing what the register holds
+    fn vi_paste_text(&mut self, text: String) {
+        self.vi.cancel();
+        self.vi.mode = Mode::Normal;
+        self.selection_anchor = None;
+        let register = mem::replace(&mut self.vi.register, text);
+        let linewise = mem::replace(&mut self.vi.linewise, false);
+        self.document.begin_transaction(&self.cursor_position);
+        self.vi_paste(false, 1);
+        self.document.end_transaction(&self.cursor_position);
+        self.vi.register = register;
+        self.vi.linewise = linewise;
+        self.clamp_vi_cursor();
+        self.scroll();
+    }
+
+    //replays the keys of the last change, a count replaces the one it was made with
+    fn repeat_change(&mut self, count: Option<usize>) -> Result<(), std::io::Error> {
+        let mut keys = self.vi.last_change.clone();
+        if let Some(count) = count {
+            let digits = keys
+                .iter()
+                .take_while(|key| matches!(key.code, Char('0'..='9')))
+                .count();
+            keys.splice(..digits, count.to_string().chars().map(|c| KeyEvent::from(Char(c))));
+        }
+        for key in &keys {
+            self.process_key_press(key)?;
+            //a change that can't be made here, like `ct)` without a `)`,
+            //doesn't go on to type its text as commands
+            if self.vi.mode == Mode::Normal && self.vi.pending().is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn handle_key_press(&mut self, key_event: &KeyEvent) -> Result<(), std::io::Error> {
+        //every edit caused by a single key press is undone as one step
+        self.document.begin_transaction(&self.cursor_position);
+        let result = self.process_key_press(key_event);
+        self.document.end_transaction(&self.cursor_position);
+        //brings the cursor back into view after scrolling with the mouse wheel
+        self.scroll();
+        result
+    }
+
+    //looks the keys up in the keymap, unbound characters are inserted
+    fn process_key_press(&mut self, key_event: &KeyEvent) -> Result<(), std::io::Error> {
+        //a keymap sequence that has been started is finished first
+        if self.config.vi_mode && self.pending_keys.is_empty() && self.handle_vi_key(key_event)? {
+            return Ok(());
+        }
+        if key_event.code == Esc && !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            self.status_message = StatusMessage::from(String::new());
+            return Ok(());
+        }
+        self.pending_keys.push(Chord::from(key_event));
+        match self.config.keymap.lookup(&self.pending_keys) {
+            Lookup::Command(command) => {
+                //the prefix shown while the sequence was typed is done with
+                if self.pending_keys.len() > 1 {
+                    self.status_message = StatusMessage::from(String::new());
                 }
+                self.pending_keys.clear();
+                self.run_command(command)?;
             }
+            Lookup::Prefix => {
+                self.status_message = StatusMessage::from(format!(
+                    "{} -",
+                    keymap::sequence_name(&self.pending_keys)
+                ));
+            }
+            Lookup::Unbound => {
+                let sequence = keymap::sequence_name(&self.pending_keys);
+                let is_sequence = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if is_sequence {
+                    self.status_message = StatusMessage::from(format!("{sequence} is not bound"));
+                } else if let KeyEvent {code: Char(c), modifiers, ..} = key_event {
+                    if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                        self.delete_selection();
+                        self.document.insert(&self.cursor_position, *c);
+                        self.move_cursor(Right)
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<(), std::io::Error> {
+        match command {
+            Command::Quit => self.quit(),
+
+            Command::NextBuffer => self.cycle_buffer(true),
+            Command::PreviousBuffer => self.cycle_buffer(false),
+            Command::ListBuffers => self.list_buffers()?,
+            Command::OpenFile => self.open_file()?,
+            Command::Browse => self.browse(PathBuf::from("."))?,
+
+            Command::SplitHorizontal => self.split_window(SplitKind::Horizontal),
+            Command::SplitVertical => self.split_window(SplitKind::Vertical),
+            Command::CloseWindow => self.close_window(),
+            Command::NextWindow => {
+                let next = self.current_window.saturating_add(1) % self.windows.len();
+                self.focus_window(next);
+            }
+            Command::WindowLeft => self.move_focus(Left),
+            Command::WindowRight => self.move_focus(Right),
+            Command::WindowUp => self.move_focus(Up),
+            Command::WindowDown => self.move_focus(Down),
+            Command::GrowWindow => self.resize_window(SplitKind::Horizontal, true),
+            Command::ShrinkWindow => self.resize_window(SplitKind::Horizontal, false),
+            Command::WidenWindow => self.resize_window(SplitKind::Vertical, true),
+            Command::NarrowWindow => self.resize_window(SplitKind::Vertical, false),
+
+            Command::Save => self.save(),
+
+            Command::Find => self.search(),
+            Command::Replace => self.replace()?,
 
-            KeyEvent {modifiers: KeyModifiers::CONTROL, code: Char('s'), ..} => self.save(),
-            
-            KeyEvent {modifiers: KeyModifiers::CONTROL, code: Left, ..} => _ = self.jump_to_word_start(), 
-            KeyEvent {modifiers: KeyModifiers::CONTROL, code: Right, ..} => _ = self.jump_to_next_word(),
+            Command::ConvertLineEndings => self.convert_line_endings(),
 
-            //also Ctrl + D for deleting entire row
-            KeyEvent{code: Up | Down | Left | Right | PageDown | PageUp | Home| End, ..} 
-                | KeyEvent{modifiers: KeyModifiers::CONTROL, code: Char('h')
-                | Char('j')
-                | Char('k')
-                | Char('l'), ..}  => {
-                let KeyEvent {code, ..} = key_event;
-                self.move_cursor(*code)
+            Command::Undo => {
+                self.selection_anchor = None;
+                self.undo();
             }
+            Command::Redo => {
+                self.selection_anchor = None;
+                self.redo();
+            }
+
+            Command::Copy => self.copy(),
+            Command::Cut => self.cut(),
+            Command::Paste => self.paste(),
 
-            KeyEvent {modifiers: KeyModifiers::CONTROL, code: Char('b'), ..} => self.delete_word(),
+            Command::SelectLeft => self.extend_selection(Command::MoveLeft),
+            Command::SelectRight => self.extend_selection(Command::MoveRight),
+            Command::SelectUp => self.extend_selection(Command::MoveUp),
+            Command::SelectDown => self.extend_selection(Command::MoveDown),
+            Command::SelectPageUp => self.extend_selection(Command::PageUp),
+            Command::SelectPageDown => self.extend_selection(Command::PageDown),
+            Command::SelectLineStart => self.extend_selection(Command::LineStart),
+            Command::SelectLineEnd => self.extend_selection(Command::LineEnd),
+            Command::SelectWordLeft => self.extend_selection(Command::WordLeft),
+            Command::SelectWordRight => self.extend_selection(Command::WordRight),
+
+            Command::MoveLeft
+            | Command::MoveRight
+            | Command::MoveUp
+            | Command::MoveDown
+            | Command::PageUp
+            | Command::PageDown
+            | Command::LineStart
+            | Command::LineEnd
+            | Command::WordLeft
+            | Command::WordRight => {
+                self.selection_anchor = None;
+                self.move_by(command);
+            }
 
-            KeyEvent {code: Delete, ..} => {
-                self.document.delete(&self.cursor_position);
+            Command::DeleteWord => {
+                self.selection_anchor = None;
+                self.delete_word();
             }
 
-            KeyEvent {code: Backspace, ..} => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
+            Command::DeleteForward => {
+                if !self.delete_selection() {
+                    self.document.delete(&self.cursor_position);
+                }
+            }
+
+            Command::DeleteBackward => {
+                if !self.delete_selection() && (self.cursor_position.x > 0 || self.cursor_position.y > 0) {
                     self.move_cursor(Left);
                     self.document.delete(&self.cursor_position);
                 }
             }
 
-            KeyEvent {code: Enter, ..} => {
+            Command::NewLine => {
+                self.delete_selection();
                 self.document.new_line(&self.cursor_position);
                 self.move_cursor(Right)
             }
 
-            KeyEvent {code: Tab, ..} => {
-                for _ in 0..4 {
-                    self.document.insert(&self.cursor_position, ' ');
-                    self.move_cursor(Right)
-                }
-            } 
+            Command::GoToLine => self.go_to_line(),
 
-            KeyEvent {code: Char(c), ..} => {
-                self.document.insert(&self.cursor_position, *c);
-                self.move_cursor(Right)
+            Command::ToggleWrap => {
+                self.config.soft_wrap = !self.config.soft_wrap;
+                self.offset.x = 0;
+                self.wrap_offset = 0;
+                self.scroll();
             }
 
-            /*
-            KeyEvent {code, ..} => {
-                println!("{code:?}");
-            } */
-
-            _ => ()  
+            Command::Indent => {
+                self.delete_selection();
+                if self.config.soft_tabs {
+                    //fills up to the next tab stop
+                    let tab_width = self.config.tab_width;
+                    let spaces = tab_width.saturating_sub(self.cursor_column().checked_rem(tab_width).unwrap_or(0));
+                    for _ in 0..spaces {
+                        self.document.insert(&self.cursor_position, ' ');
+                        self.move_cursor(Right)
+                    }
+                } else {
+                    self.document.insert(&self.cursor_position, '\t');
+                    self.move_cursor(Right)
+                }
+            }
         }
         Ok(())
     }
 }
+
+//splits `file:line` and `file:line:column` as printed by compilers and grep,
+//unless a file with the whole name exists
+fn split_location(arg: &str) -> (&str, Option<(usize, usize)>) {
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+    let trimmed = arg.strip_suffix(':').unwrap_or(arg);
+    let mut parts = trimmed.rsplitn(3, ':');
+    let numbers: Vec<Option<usize>> = parts
+        .by_ref()
+        .take(2)
+        .map(|part| part.parse::<usize>().ok())
+        .collect();
+    match (numbers.as_slice(), parts.next()) {
+        ([Some(column), Some(line)], Some(filename)) => (filename, Some((*line, *column))),
+        _ => match trimmed.rsplit_once(':') {
+            Some((filename, line)) => match line.parse::<usize>() {
+                Ok(line) => (filename, Some((line, 1))),
+                Err(_) => (arg, None),
+            },
+            None => (arg, None),
+        },
+    }
+}
+
+//parses `line`, `line:column`, `+N`, `-N` or `N%` into a row and column counted from 0
+#[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
+fn parse_location(text: &str, current: usize, len: usize) -> Option<(usize, usize)> {
+    if let Some(percent) = text.strip_suffix('%') {
+        let percent = percent.trim().parse::<usize>().ok().filter(|percent| *percent <= 100)?;
+        return Some((len.saturating_sub(1).saturating_mul(percent) / 100, 0));
+    }
+    if let Some(offset) = text.strip_prefix('+') {
+        return Some((current.saturating_add(offset.trim().parse().ok()?), 0));
+    }
+    if let Some(offset) = text.strip_prefix('-') {
+        return Some((current.saturating_sub(offset.trim().parse().ok()?), 0));
+    }
+    let (line, column) = text.split_once(':').unwrap_or((text, "1"));
+    let line = line.trim().parse::<usize>().ok()?;
+    let column = column.trim().parse::<usize>().ok()?;
+    Some((line.saturating_sub(1), column.saturating_sub(1)))
+}
+
+//lists the keys bound to the most common commands
+fn help_message(config: &Config) -> String {
+    let entries: Vec<String> = [
+        (Command::Save, "save"),
+        (Command::OpenFile, "open"),
+        (Command::Quit, "quit"),
+        (Command::Find, "find"),
+        (Command::Replace, "replace"),
+        (Command::Undo, "undo"),
+        (Command::Redo, "redo"),
+        (Command::Cut, "cut"),
+        (Command::Copy, "copy"),
+        (Command::Paste, "paste"),
+    ]
+    .iter()
+    .filter_map(|(command, name)| {
+        config.keymap.keys_for(*command).map(|keys| format!("{keys} = {name}"))
+    })
+    .collect();
+    format!("HELP: {}", entries.join(", "))
+}
+
+//names typed in the browser are kept inside the directory they are used in
+fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(rope::width).sum()
+}
+
+//the start of the text that fits in `width` columns, cut between graphemes
+fn fit_width(text: &str, width: usize) -> String {
+    let mut columns: usize = 0;
+    text.graphemes(true)
+        .take_while(|grapheme| {
+            columns = columns.saturating_add(rope::width(grapheme));
+            columns <= width
+        })
+        .collect()
+}
+
+fn relative_name(name: &str) -> Result<&Path, io::Error> {
+    let path = Path::new(name);
+    if path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        Ok(path)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name} is outside of the directory"),
+        ))
+    }
+}
+
+//the documents of every buffer, including the one the browser is shown in place of
+fn open_documents<'a>(buffers: &'a mut [Buffer], current: &'a mut Document) -> impl Iterator<Item = &'a mut Document> {
+    buffers
+        .iter_mut()
+        .map(|buffer| &mut buffer.document)
+        .chain(std::iter::once(current))
+}
+
+//the tree as lines of a document, named after the directory in the status bar
+fn tree_document(tree: &Tree, entries: &[Entry]) -> Document {
+    let lines: Vec<String> = entries.iter().map(|entry| tree.line(entry)).collect();
+    let mut document = Document::from_text(&lines.join("\n"));
+    document.filename = Some(format!("{}/", tree.root().display()));
+    document
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct FileType {
+    name: &'static str,
+    hl_opts: HighlightingOptions,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct HighlightingOptions {
+    numbers: bool,
+    characters: bool,
+    headings: bool,
+    strings: &'static [char],
+    comment: Option<&'static str>,
+    multiline_comment: Option<(&'static str, &'static str)>,
+    primary_keywords: &'static [&'static str],
+    secondary_keywords: &'static [&'static str],
+}
+
+impl Default for FileType {
+    fn default() -> Self {
+        Self {
+            name: "No filetype",
+            hl_opts: HighlightingOptions::default(),
+        }
+    }
+}
+
+impl FileType {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn highlighting_options(&self) -> &HighlightingOptions {
+        &self.hl_opts
+    }
+
+    //picks the filetype from the extension of the file name
+    pub fn from(filename: &str) -> Self {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        match extension {
+            "rs" => Self {
+                name: "Rust",
+                hl_opts: HighlightingOptions {
+                    numbers: true,
+                    characters: true,
+                    strings: &['"'],
+                    comment: Some("//"),
+                    multiline_comment: Some(("/*", "*/")),
+                    primary_keywords: RUST_PRIMARY_KEYWORDS,
+                    secondary_keywords: RUST_SECONDARY_KEYWORDS,
+                    ..HighlightingOptions::default()
+                },
+            },
+            "c" | "h" => Self {
+                name: "C",
+                hl_opts: HighlightingOptions {
+                    numbers: true,
+                    characters: true,
+                    strings: &['"'],
+                    comment: Some("//"),
+                    multiline_comment: Some(("/*", "*/")),
+                    primary_keywords: C_PRIMARY_KEYWORDS,
+                    secondary_keywords: C_SECONDARY_KEYWORDS,
+                    ..HighlightingOptions::default()
+                },
+            },
+            "py" => Self {
+                name: "Python",
+                hl_opts: HighlightingOptions {
+                    numbers: true,
+                    strings: &['"', '\''],
+                    comment: Some("#"),
+                    multiline_comment: Some(("\"\"\"", "\"\"\"")),
+                    primary_keywords: PYTHON_PRIMARY_KEYWORDS,
+                    secondary_keywords: PYTHON_SECONDARY_KEYWORDS,
+                    ..HighlightingOptions::default()
+                },
+            },
+            "sh" | "bash" | "zsh" => Self {
+                name: "Shell",
+                hl_opts: HighlightingOptions {
+                    numbers: true,
+                    strings: &['"', '\''],
+                    comment: Some("#"),
+                    primary_keywords: SHELL_PRIMARY_KEYWORDS,
+                    secondary_keywords: SHELL_SECONDARY_KEYWORDS,
+                    ..HighlightingOptions::default()
+                },
+            },
+            "md" | "markdown" => Self {
+                name: "Markdown",
+                hl_opts: HighlightingOptions {
+                    headings: true,
+                    strings: &['`'],
+                    multiline_comment: Some(("```", "```")),
+                    ..HighlightingOptions::default()
+                },
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+impl HighlightingOptions {
+    pub fn numbers(&self) -> bool {
+        self.numbers
+    }
+
+    pub fn characters(&self) -> bool {
+        self.characters
+    }
+
+    //lines starting with '#' are highlighted as headings
+    pub fn headings(&self) -> bool {
+        self.headings
+    }
+
+    pub fn strings(&self) -> &'static [char] {
+        self.strings
+    }
+
+    pub fn comment(&self) -> Option<&'static str> {
+        self.comment
+    }
+
+    pub fn multiline_comment(&self) -> Option<(&'static str, &'static str)> {
+        self.multiline_comment
+    }
+
+    pub fn primary_keywords(&self) -> &'static [&'static str] {
+        self.primary_keywords
+    }
+
+    pub fn secondary_keywords(&self) -> &'static [&'static str] {
+        self.secondary_keywords
+    }
+}
+
+const RUST_PRIMARY_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while",
+];
+
+const RUST_SECONDARY_KEYWORDS: &[&str] = &[
+    "bool", "char", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+    "u128", "usize", "f32", "f64", "str", "String", "Vec", "Box", "Option", "Result", "Some",
+    "None", "Ok", "Err",
+];
+
+const C_PRIMARY_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern",
+    "for", "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static",
+    "struct", "switch", "typedef", "union", "volatile", "while", "#include", "#define", "#if",
+    "#ifdef", "#ifndef", "#else", "#endif", "#pragma",
+];
+
+const C_SECONDARY_KEYWORDS: &[&str] = &[
+    "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "bool",
+    "size_t", "NULL", "true", "false",
+];
+
+const PYTHON_PRIMARY_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+    "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+    "yield", "True", "False", "None",
+];
+
+const PYTHON_SECONDARY_KEYWORDS: &[&str] = &[
+    "bool", "bytes", "dict", "float", "int", "len", "list", "object", "print", "range", "self",
+    "set", "str", "tuple",
+];
+
+const SHELL_PRIMARY_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "case", "esac", "for", "while", "until", "do", "done",
+    "in", "function", "return", "local", "export", "select", "break", "continue", "exit",
+];
+
+const SHELL_SECONDARY_KEYWORDS: &[&str] = &[
+    "echo", "printf", "read", "cd", "test", "set", "unset", "source", "alias", "eval", "exec",
+    "shift", "trap",
+];
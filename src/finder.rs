@@ -0,0 +1,245 @@
+//lists the files of a project and ranks them against a fuzzy query
+
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+//stops walking huge trees, like a home directory, from freezing the editor
+const MAX_FILES: usize = 50_000;
+
+//a line of a .gitignore file
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    //patterns ending in `/` only match directories
+    dir_only: bool,
+    //patterns with a `/` before their end match the whole path from the
+    //directory of the .gitignore, the others match the name at any depth
+    anchored: bool,
+}
+
+//the patterns of the .gitignore in `base`, relative to the project
+struct Ignore {
+    base: String,
+    patterns: Vec<Pattern>,
+}
+
+//every file under the directory as a `/` separated relative path, sorted,
+//leaving out .git and whatever the .gitignore files along the way ignore
+pub fn files(root: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut ignores = Vec::new();
+    walk(root, "", &mut ignores, &mut files);
+    files
+}
+
+fn walk(root: &Path, dir: &str, ignores: &mut Vec<Ignore>, files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(root.join(dir)) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    let pushed = match fs::read_to_string(root.join(dir).join(".gitignore")) {
+        Ok(text) => {
+            ignores.push(Ignore {
+                base: dir.to_string(),
+                patterns: text.lines().filter_map(parse_pattern).collect(),
+            });
+            true
+        }
+        Err(_) => false,
+    };
+
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let path = if dir.is_empty() { name.clone() } else { format!("{dir}/{name}") };
+        //symbolic links to directories are not followed so cycles can't happen
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+        if name == ".git" || is_ignored(ignores, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk(root, &path, ignores, files);
+        } else if file_type.is_file() || entry.path().is_file() {
+            files.push(path);
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+}
+
+//the last pattern that matches decides, so deeper files override their parents
+fn is_ignored(ignores: &[Ignore], path: &str, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for ignore in ignores {
+        let relative = if ignore.base.is_empty() {
+            path
+        } else {
+            match path.strip_prefix(&ignore.base).and_then(|rest| rest.strip_prefix('/')) {
+                Some(relative) => relative,
+                None => continue,
+            }
+        };
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        for pattern in &ignore.patterns {
+            if pattern.dir_only && !is_dir {
+                continue;
+            }
+            let subject = if pattern.anchored { relative } else { name };
+            if pattern.regex.is_match(subject) {
+                ignored = !pattern.negated;
+            }
+        }
+    }
+    ignored
+}
+
+fn parse_pattern(line: &str) -> Option<Pattern> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let regex = Regex::new(&format!("^{}$", glob_to_regex(line))).ok()?;
+    Some(Pattern {regex, negated, dir_only, anchored})
+}
+
+//`*` and `?` stay within a path component, `**` crosses them
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == '\\' {
+                        regex.push_str("\\\\");
+                        continue;
+                    }
+                    regex.push(c);
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
+
+//how well the query matches the path, higher is better, None if the query's
+//characters don't all appear in order; the query is case sensitive only
+//when it contains an uppercase letter
+#[allow(
+    clippy::arithmetic_side_effects,
+    clippy::indexing_slicing,
+    clippy::integer_division,
+    clippy::as_conversions,
+    clippy::cast_possible_wrap
+)]
+pub fn score(query: &str, path: &str) -> Option<i64> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_ascii_lowercase() };
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let original: Vec<char> = path.chars().collect();
+    let candidate: Vec<char> = original.iter().copied().map(fold).collect();
+    let Some(&first) = query.first() else {
+        return Some(0);
+    };
+    let name_start = original.iter().rposition(|c| *c == '/').map_or(0, |slash| slash + 1);
+
+    //tries every place the first character matches and keeps the best
+    let mut best = None;
+    for start in (0..candidate.len()).filter(|index| candidate[*index] == first) {
+        let mut score = 0;
+        let mut previous: Option<usize> = None;
+        let mut index = start;
+        let mut matched = 0;
+        while matched < query.len() && index < candidate.len() {
+            if candidate[index] == query[matched] {
+                score += 16;
+                match previous {
+                    Some(previous) if previous + 1 == index => score += 12,
+                    Some(previous) => score -= (index - previous - 1).min(8) as i64,
+                    None => (),
+                }
+                let at_boundary = index == 0
+                    || matches!(original[index - 1], '/' | '_' | '-' | '.' | ' ')
+                    || (original[index - 1].is_lowercase() && original[index].is_uppercase());
+                if at_boundary {
+                    score += 8;
+                }
+                if index >= name_start {
+                    score += 4;
+                }
+                previous = Some(index);
+                matched += 1;
+            }
+            index += 1;
+        }
+        if matched == query.len() && best.map_or(true, |best| score > best) {
+            best = Some(score);
+        }
+    }
+    //shorter paths win between otherwise equal matches
+    best.map(|best| best - (candidate.len() as i64 / 8))
+}
+
+//the indexes of the paths that match, best first
+#[allow(clippy::indexing_slicing)]
+pub fn rank(query: &str, paths: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = paths
+        .iter()
+        .enumerate()
+        .filter_map(|(index, path)| score(query, path).map(|score| (score, index)))
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| paths[*a].len().cmp(&paths[*b].len()))
+            .then_with(|| paths[*a].cmp(&paths[*b]))
+    });
+    scored.into_iter().map(|(_, index)| index).collect()
+}
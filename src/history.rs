@@ -0,0 +1,139 @@
+use std::mem;
+use crate::editor::Position;
+
+//a single reversible change, start and end are the positions
+//the text spans while it is present in the document
+pub enum Edit {
+    Insert { start: Position, end: Position, text: String },
+    Delete { start: Position, end: Position, text: String },
+}
+
+impl Edit {
+    fn start(&self) -> Position {
+        match self {
+            Self::Insert { start, .. } | Self::Delete { start, .. } => *start,
+        }
+    }
+}
+
+//a group of edits that is undone and redone as one step
+pub struct Transaction {
+    pub edits: Vec<Edit>,
+    pub cursor_before: Position,
+    pub cursor_after: Position,
+    id: usize,
+}
+
+impl Transaction {
+    fn new(cursor: Position) -> Self {
+        Self {
+            edits: Vec::new(),
+            cursor_before: cursor,
+            cursor_after: cursor,
+            id: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    pending: Option<Transaction>,
+    depth: usize,
+    next_id: usize,
+}
+
+impl History {
+    //transactions can be nested, only the outermost one is committed
+    pub fn begin(&mut self, cursor: Position) {
+        if self.depth == 0 {
+            self.pending = Some(Transaction::new(cursor));
+        }
+        self.depth = self.depth.saturating_add(1);
+    }
+
+    pub fn end(&mut self, cursor: Position) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth > 0 {
+            return;
+        }
+        if let Some(mut transaction) = self.pending.take() {
+            if !transaction.edits.is_empty() {
+                transaction.cursor_after = cursor;
+                self.commit(transaction);
+            }
+        }
+    }
+
+    //ends every open transaction, for when the document stops being edited
+    //in the middle of them
+    pub fn close(&mut self, cursor: Position) {
+        if self.depth > 0 {
+            self.depth = 1;
+            self.end(cursor);
+        }
+    }
+
+    //edits made outside of a transaction become their own undo step
+    pub fn record(&mut self, edit: Edit) {
+        if let Some(transaction) = &mut self.pending {
+            transaction.edits.push(edit);
+            //every edit is a new revision even before the transaction is
+            //committed, long ones like vi's insert mode still mark the document dirty
+            self.next_id = self.next_id.saturating_add(1);
+            transaction.id = self.next_id;
+        } else {
+            let mut transaction = Transaction::new(edit.start());
+            transaction.edits.push(edit);
+            self.commit(transaction);
+        }
+    }
+
+    //commits what the open transaction holds so far as its own step and
+    //continues with a new one, so undo works in the middle of a transaction
+    pub fn checkpoint(&mut self, cursor: Position) {
+        let Some(pending) = &mut self.pending else {
+            return;
+        };
+        let mut transaction = mem::replace(pending, Transaction::new(cursor));
+        if !transaction.edits.is_empty() {
+            transaction.cursor_after = cursor;
+            self.commit(transaction);
+        }
+    }
+
+    fn commit(&mut self, mut transaction: Transaction) {
+        if transaction.id == 0 {
+            self.next_id = self.next_id.saturating_add(1);
+            transaction.id = self.next_id;
+        }
+        self.undo_stack.push(transaction);
+        self.redo_stack.clear();
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Transaction> {
+        self.undo_stack.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Transaction> {
+        self.redo_stack.pop()
+    }
+
+    pub fn push_undo(&mut self, transaction: Transaction) {
+        self.undo_stack.push(transaction);
+    }
+
+    pub fn push_redo(&mut self, transaction: Transaction) {
+        self.redo_stack.push(transaction);
+    }
+
+    //identifies the current state of the document, used for dirty tracking
+    pub fn revision(&self) -> Option<usize> {
+        self.pending
+            .as_ref()
+            .filter(|transaction| !transaction.edits.is_empty())
+            .or_else(|| self.undo_stack.last())
+            .map(|transaction| transaction.id)
+    }
+}
@@ -0,0 +1,372 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+//everything a key can be bound to, keys that are not bound and have no
+//ctrl or alt insert their character
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Quit,
+    Save,
+    Find,
+    Replace,
+    ConvertLineEndings,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    LineStart,
+    LineEnd,
+    WordLeft,
+    WordRight,
+    SelectLeft,
+    SelectRight,
+    SelectUp,
+    SelectDown,
+    SelectPageUp,
+    SelectPageDown,
+    SelectLineStart,
+    SelectLineEnd,
+    SelectWordLeft,
+    SelectWordRight,
+    DeleteForward,
+    DeleteBackward,
+    DeleteWord,
+    NewLine,
+    Indent,
+    ToggleWrap,
+    GoToLine,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    OpenFile,
+    Browse,
+    SplitHorizontal,
+    SplitVertical,
+    CloseWindow,
+    NextWindow,
+    WindowLeft,
+    WindowRight,
+    WindowUp,
+    WindowDown,
+    GrowWindow,
+    ShrinkWindow,
+    WidenWindow,
+    NarrowWindow,
+}
+
+const COMMANDS: [(&str, Command); 54] = [
+    ("quit", Command::Quit),
+    ("save", Command::Save),
+    ("find", Command::Find),
+    ("replace", Command::Replace),
+    ("convert-line-endings", Command::ConvertLineEndings),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("copy", Command::Copy),
+    ("cut", Command::Cut),
+    ("paste", Command::Paste),
+    ("move-left", Command::MoveLeft),
+    ("move-right", Command::MoveRight),
+    ("move-up", Command::MoveUp),
+    ("move-down", Command::MoveDown),
+    ("page-up", Command::PageUp),
+    ("page-down", Command::PageDown),
+    ("line-start", Command::LineStart),
+    ("line-end", Command::LineEnd),
+    ("word-left", Command::WordLeft),
+    ("word-right", Command::WordRight),
+    ("select-left", Command::SelectLeft),
+    ("select-right", Command::SelectRight),
+    ("select-up", Command::SelectUp),
+    ("select-down", Command::SelectDown),
+    ("select-page-up", Command::SelectPageUp),
+    ("select-page-down", Command::SelectPageDown),
+    ("select-line-start", Command::SelectLineStart),
+    ("select-line-end", Command::SelectLineEnd),
+    ("select-word-left", Command::SelectWordLeft),
+    ("select-word-right", Command::SelectWordRight),
+    ("delete-forward", Command::DeleteForward),
+    ("delete-backward", Command::DeleteBackward),
+    ("delete-word", Command::DeleteWord),
+    ("new-line", Command::NewLine),
+    ("indent", Command::Indent),
+    ("toggle-wrap", Command::ToggleWrap),
+    ("go-to-line", Command::GoToLine),
+    ("next-buffer", Command::NextBuffer),
+    ("previous-buffer", Command::PreviousBuffer),
+    ("list-buffers", Command::ListBuffers),
+    ("open-file", Command::OpenFile),
+    ("browse", Command::Browse),
+    ("split-horizontal", Command::SplitHorizontal),
+    ("split-vertical", Command::SplitVertical),
+    ("close-window", Command::CloseWindow),
+    ("next-window", Command::NextWindow),
+    ("window-left", Command::WindowLeft),
+    ("window-right", Command::WindowRight),
+    ("window-up", Command::WindowUp),
+    ("window-down", Command::WindowDown),
+    ("grow-window", Command::GrowWindow),
+    ("shrink-window", Command::ShrinkWindow),
+    ("widen-window", Command::WidenWindow),
+    ("narrow-window", Command::NarrowWindow),
+];
+
+const DEFAULT_BINDINGS: [(&str, Command); 58] = [
+    ("ctrl-q", Command::Quit),
+    ("ctrl-s", Command::Save),
+    ("ctrl-f", Command::Find),
+    ("ctrl-r", Command::Replace),
+    ("ctrl-e", Command::ConvertLineEndings),
+    ("ctrl-z", Command::Undo),
+    ("ctrl-y", Command::Redo),
+    ("ctrl-c", Command::Copy),
+    ("ctrl-x", Command::Cut),
+    ("ctrl-v", Command::Paste),
+    ("left", Command::MoveLeft),
+    ("ctrl-h", Command::MoveLeft),
+    ("right", Command::MoveRight),
+    ("ctrl-l", Command::MoveRight),
+    ("up", Command::MoveUp),
+    ("ctrl-k", Command::MoveUp),
+    ("down", Command::MoveDown),
+    ("ctrl-j", Command::MoveDown),
+    ("pageup", Command::PageUp),
+    ("pagedown", Command::PageDown),
+    ("home", Command::LineStart),
+    ("end", Command::LineEnd),
+    ("ctrl-left", Command::WordLeft),
+    ("ctrl-right", Command::WordRight),
+    ("shift-left", Command::SelectLeft),
+    ("shift-right", Command::SelectRight),
+    ("shift-up", Command::SelectUp),
+    ("shift-down", Command::SelectDown),
+    ("shift-pageup", Command::SelectPageUp),
+    ("shift-pagedown", Command::SelectPageDown),
+    ("shift-home", Command::SelectLineStart),
+    ("shift-end", Command::SelectLineEnd),
+    ("ctrl-shift-left", Command::SelectWordLeft),
+    ("ctrl-shift-right", Command::SelectWordRight),
+    ("delete", Command::DeleteForward),
+    ("backspace", Command::DeleteBackward),
+    ("ctrl-b", Command::DeleteWord),
+    ("enter", Command::NewLine),
+    ("tab", Command::Indent),
+    ("alt-z", Command::ToggleWrap),
+    ("ctrl-g", Command::GoToLine),
+    ("ctrl-pagedown", Command::NextBuffer),
+    ("ctrl-pageup", Command::PreviousBuffer),
+    ("alt-b", Command::ListBuffers),
+    ("ctrl-o", Command::OpenFile),
+    ("alt-e", Command::Browse),
+    ("ctrl-w s", Command::SplitHorizontal),
+    ("ctrl-w v", Command::SplitVertical),
+    ("ctrl-w q", Command::CloseWindow),
+    ("ctrl-w w", Command::NextWindow),
+    ("ctrl-w h", Command::WindowLeft),
+    ("ctrl-w l", Command::WindowRight),
+    ("ctrl-w k", Command::WindowUp),
+    ("ctrl-w j", Command::WindowDown),
+    ("ctrl-w +", Command::GrowWindow),
+    ("ctrl-w -", Command::ShrinkWindow),
+    ("ctrl-w >", Command::WidenWindow),
+    ("ctrl-w <", Command::NarrowWindow),
+];
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        COMMANDS
+            .iter()
+            .find(|(command_name, _)| *command_name == name)
+            .map(|(_, command)| *command)
+    }
+}
+
+//a key together with its modifiers, shift is left out for characters
+//since it is already part of the character
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Chord {
+    pub fn from(key_event: &KeyEvent) -> Self {
+        let mut modifiers =
+            key_event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if let KeyCode::Char(_) = key_event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: key_event.code,
+            modifiers,
+        }
+    }
+
+    //parses chords like `ctrl-x`, `ctrl-shift-left`, `alt-enter` or `f5`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let error = || format!("unknown key `{text}`");
+        //the last part is the key, so `ctrl--` is ctrl and minus
+        let (modifier_names, key) = if text == "-" {
+            ("", text)
+        } else if let Some(modifiers) = text.strip_suffix("--") {
+            (modifiers, "-")
+        } else {
+            text.rsplit_once('-').unwrap_or(("", text))
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('-').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(error()),
+            };
+        }
+        let code = match key.to_lowercase().as_str() {
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            name => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => name
+                        .strip_prefix('f')
+                        .and_then(|number| number.parse::<u8>().ok())
+                        .filter(|number| (1..=12).contains(number))
+                        .map(KeyCode::F)
+                        .ok_or_else(error)?,
+                }
+            }
+        };
+        Ok(Self::from(&KeyEvent::new(code, modifiers)))
+    }
+
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for (modifier, modifier_name) in [
+            (KeyModifiers::CONTROL, "Ctrl-"),
+            (KeyModifiers::ALT, "Alt-"),
+            (KeyModifiers::SHIFT, "Shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                name.push_str(modifier_name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => name.push_str("Space"),
+            //control characters are shown the usual way, as Ctrl-S
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                name.extend(c.to_uppercase());
+            }
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::F(number) => name.push_str(&format!("F{number}")),
+            code => name.push_str(&format!("{code:?}")),
+        }
+        name
+    }
+}
+
+pub enum Lookup {
+    Command(Command),
+    //the keys so far start a longer sequence
+    Prefix,
+    Unbound,
+}
+
+//maps sequences of one or more chords to commands
+pub struct Keymap {
+    bindings: HashMap<Vec<Chord>, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        for (keys, command) in DEFAULT_BINDINGS {
+            if let Ok(sequence) = parse_sequence(keys) {
+                keymap.bindings.insert(sequence, command);
+            }
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    //binds space separated chords like `ctrl-x ctrl-s` to a command name,
+    //or removes the binding when the name is `none`
+    pub fn bind(&mut self, keys: &str, name: &str) -> Result<(), String> {
+        let sequence = parse_sequence(keys)?;
+        if name == "none" {
+            self.bindings.remove(&sequence);
+        } else {
+            let command =
+                Command::from_name(name).ok_or_else(|| format!("unknown command `{name}`"))?;
+            self.bindings.insert(sequence, command);
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, sequence: &[Chord]) -> Lookup {
+        if let Some(command) = self.bindings.get(sequence) {
+            return Lookup::Command(*command);
+        }
+        let is_prefix = self
+            .bindings
+            .keys()
+            .any(|keys| keys.len() > sequence.len() && keys.starts_with(sequence));
+        if is_prefix {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+
+    //the shortest sequence bound to the command, for help messages
+    pub fn keys_for(&self, command: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == command)
+            .map(|(keys, _)| sequence_name(keys))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+    }
+}
+
+pub fn sequence_name(sequence: &[Chord]) -> String {
+    sequence
+        .iter()
+        .map(Chord::name)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_sequence(keys: &str) -> Result<Vec<Chord>, String> {
+    let sequence = keys
+        .split_whitespace()
+        .map(Chord::parse)
+        .collect::<Result<Vec<Chord>, String>>()?;
+    if sequence.is_empty() {
+        return Err("missing keys to bind".to_string());
+    }
+    Ok(sequence)
+}
@@ -0,0 +1,161 @@
+//a part of the screen, in cells
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&x)
+            && (self.y..self.y.saturating_add(self.height)).contains(&y)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitKind {
+    //one window above the other
+    Horizontal,
+    //windows side by side with a separator column between them
+    Vertical,
+}
+
+//share of the first window in a new split, in percent
+const EVEN: usize = 50;
+const MIN_PERCENT: usize = 10;
+const MAX_PERCENT: usize = 90;
+
+//how the screen is divided between windows, identified by their index
+pub enum Layout {
+    Window(usize),
+    Split {
+        kind: SplitKind,
+        percent: usize,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    //puts the new window next to the given one, splitting its space evenly
+    pub fn split(&mut self, window: usize, new_window: usize, kind: SplitKind) -> bool {
+        match self {
+            Self::Window(index) if *index == window => {
+                *self = Self::Split {
+                    kind,
+                    percent: EVEN,
+                    first: Box::new(Self::Window(window)),
+                    second: Box::new(Self::Window(new_window)),
+                };
+                true
+            }
+            Self::Window(_) => false,
+            Self::Split { first, second, .. } => {
+                first.split(window, new_window, kind) || second.split(window, new_window, kind)
+            }
+        }
+    }
+
+    //gives the space of the window to its sibling and renumbers the windows
+    //after it, so the indexes keep matching the editor's list of windows
+    pub fn remove(&mut self, window: usize) {
+        self.remove_window(window);
+        self.renumber(window);
+    }
+
+    fn remove_window(&mut self, window: usize) -> bool {
+        let Self::Split { first, second, .. } = self else {
+            return false;
+        };
+        let sibling = match (&**first, &**second) {
+            (Self::Window(index), _) if *index == window => second,
+            (_, Self::Window(index)) if *index == window => first,
+            _ => return first.remove_window(window) || second.remove_window(window),
+        };
+        let sibling = std::mem::replace(&mut **sibling, Self::Window(window));
+        *self = sibling;
+        true
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Self::Window(index) if *index > removed => *index = index.saturating_sub(1),
+            Self::Window(_) => (),
+            Self::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    //grows the window by moving the closest split of the given kind around it
+    pub fn resize(&mut self, window: usize, kind: SplitKind, grow: bool, step: usize) -> bool {
+        let Self::Split { kind: split_kind, percent, first, second } = self else {
+            return false;
+        };
+        let in_first = first.contains(window);
+        if !in_first && !second.contains(window) {
+            return false;
+        }
+        let inner = if in_first { first } else { second };
+        if inner.resize(window, kind, grow, step) {
+            return true;
+        }
+        if *split_kind != kind {
+            return false;
+        }
+        //growing the second window means moving the split towards the first
+        *percent = if grow == in_first {
+            percent.saturating_add(step)
+        } else {
+            percent.saturating_sub(step)
+        }
+        .clamp(MIN_PERCENT, MAX_PERCENT);
+        true
+    }
+
+    fn contains(&self, window: usize) -> bool {
+        match self {
+            Self::Window(index) => *index == window,
+            Self::Split { first, second, .. } => first.contains(window) || second.contains(window),
+        }
+    }
+
+    //the area of every window and of the separators between side by side windows
+    #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
+    pub fn rects(&self, area: Rect, windows: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+        match self {
+            Self::Window(index) => windows.push((*index, area)),
+            Self::Split { kind: SplitKind::Horizontal, percent, first, second } => {
+                let height = (area.height * percent / 100).clamp(1, area.height.saturating_sub(1).max(1));
+                first.rects(Rect { height, ..area }, windows, separators);
+                second.rects(
+                    Rect {
+                        y: area.y + height,
+                        height: area.height.saturating_sub(height),
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+            }
+            Self::Split { kind: SplitKind::Vertical, percent, first, second } => {
+                let available = area.width.saturating_sub(1);
+                let width = (available * percent / 100).clamp(1, available.saturating_sub(1).max(1));
+                first.rects(Rect { width, ..area }, windows, separators);
+                separators.push(Rect { x: area.x + width, width: 1, ..area });
+                second.rects(
+                    Rect {
+                        x: area.x + width + 1,
+                        width: available.saturating_sub(width),
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+#![warn(clippy::all, clippy::pedantic, clippy::restriction)]
+#![allow(
+    clippy::missing_docs_in_private_items,
+    clippy::implicit_return,
+    clippy::shadow_reuse,
+    clippy::print_stdout,
+    clippy::wildcard_enum_match_arm,
+    clippy::else_if_without_else
+)]
+mod editor;
+mod terminal;
+mod document;
+mod row;
+mod rope;
+mod history;
+mod highlighting;
+mod filetype;
+mod swap;
+mod diff;
+mod clipboard;
+mod config;
+mod keymap;
+mod layout;
+mod finder;
+mod browser;
+mod vi;
+
+pub use document::Document;
+pub use filetype::FileType;
+pub use row::Row;
+pub use terminal::Terminal;
+pub use editor::{Editor, Position};
@@ -7,15 +7,7 @@
     clippy::wildcard_enum_match_arm,
     clippy::else_if_without_else
 )]
-mod editor;
-mod terminal;
-mod document;
-mod row;
-
-pub use document::Document;
-pub use row::Row;
-pub use terminal::Terminal;
-use editor::Editor;
+use kibi::Editor;
 
 fn main() {
     //Error now bubbles up, how should it be handled?
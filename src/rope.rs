@@ -0,0 +1,510 @@
+//the text of a row as a tree of chunks, so an edit in a huge line only
+//rewrites one chunk and the nodes above it instead of the whole line
+
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+//chunks are split once they grow past this many bytes
+const MAX_CHUNK: usize = 1024;
+const MAX_CHILDREN: usize = 16;
+
+//what a subtree holds, kept in every node so lookups can skip whole subtrees
+#[derive(Clone, Copy, Default)]
+struct Summary {
+    bytes: usize,
+    graphemes: usize,
+    //the width of everything but tabs, whose width depends on the column
+    width: usize,
+    tabs: bool,
+}
+
+impl Summary {
+    //chunks only ever start and end between graphemes, so each one is
+    //segmented on its own
+    #[allow(clippy::arithmetic_side_effects)]
+    fn of(text: &str) -> Self {
+        if text.is_ascii() {
+            let tabs = text.bytes().filter(|byte| *byte == b'\t').count();
+            return Self {
+                bytes: text.len(),
+                graphemes: text.len(),
+                width: text.len() - tabs,
+                tabs: tabs > 0,
+            };
+        }
+        let mut summary = Self {
+            bytes: text.len(),
+            ..Self::default()
+        };
+        for grapheme in text.graphemes(true) {
+            summary.graphemes += 1;
+            if grapheme == "\t" {
+                summary.tabs = true;
+            } else {
+                summary.width += width(grapheme);
+            }
+        }
+        summary
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes.saturating_add(other.bytes),
+            graphemes: self.graphemes.saturating_add(other.graphemes),
+            width: self.width.saturating_add(other.width),
+            tabs: self.tabs || other.tabs,
+        }
+    }
+}
+
+pub fn width(grapheme: &str) -> usize {
+    if grapheme.is_ascii() {
+        1
+    } else {
+        grapheme.width()
+    }
+}
+
+#[derive(Clone)]
+enum Kind {
+    Leaf(String),
+    Branch(Vec<Rc<Node>>),
+}
+
+#[derive(Clone)]
+struct Node {
+    summary: Summary,
+    kind: Kind,
+}
+
+//where a walk over the graphemes starts
+#[derive(Clone, Copy)]
+pub enum Seek {
+    Index(usize),
+    Column(usize),
+}
+
+#[derive(Clone)]
+pub struct Rope {
+    root: Rc<Node>,
+}
+
+impl Default for Rope {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        let mut nodes: Vec<Rc<Node>> = chunks(text).into_iter().map(|chunk| Rc::new(Node::leaf(chunk))).collect();
+        while nodes.len() > 1 {
+            nodes = group(nodes).into_iter().map(|children| Rc::new(Node::branch(children))).collect();
+        }
+        Self {
+            root: nodes.pop().unwrap_or_else(|| Rc::new(Node::leaf(String::new()))),
+        }
+    }
+}
+
+impl Rope {
+    pub fn len(&self) -> usize {
+        self.root.summary.graphemes
+    }
+
+    pub fn byte_len(&self) -> usize {
+        self.root.summary.bytes
+    }
+
+    //ascii text has one byte per grapheme, "\r\n" never appears inside a row
+    pub fn is_ascii(&self) -> bool {
+        self.root.summary.bytes == self.root.summary.graphemes
+    }
+
+    pub fn chunks(&self) -> Vec<&str> {
+        let mut chunks = Vec::new();
+        self.root.collect_chunks(&mut chunks);
+        chunks
+    }
+
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        if start < end {
+            self.root.slice(start, end, &mut text);
+        }
+        text
+    }
+
+    //inserts the text before the grapheme at index
+    pub fn insert(&mut self, index: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let index = index.min(self.len());
+        let extra = Rc::make_mut(&mut self.root).insert(index, text);
+        if extra.is_empty() {
+            return;
+        }
+        //the root split, so the tree grows a level
+        let mut nodes = vec![Rc::clone(&self.root)];
+        nodes.extend(extra);
+        while nodes.len() > 1 {
+            nodes = group(nodes).into_iter().map(|children| Rc::new(Node::branch(children))).collect();
+        }
+        if let Some(root) = nodes.pop() {
+            self.root = root;
+        }
+    }
+
+    //removes the graphemes in start..end and returns them
+    pub fn remove(&mut self, start: usize, end: usize) -> String {
+        let mut removed = String::new();
+        let end = end.min(self.len());
+        if start >= end {
+            return removed;
+        }
+        Rc::make_mut(&mut self.root).remove(start, end, &mut removed);
+        //removing can leave a chain of single children at the top
+        loop {
+            let child = match &self.root.kind {
+                Kind::Branch(children) if children.len() <= 1 => children.first().cloned(),
+                _ => break,
+            };
+            self.root = child.unwrap_or_else(|| Rc::new(Node::leaf(String::new())));
+        }
+        removed
+    }
+
+    pub fn byte_index(&self, index: usize) -> usize {
+        if self.is_ascii() {
+            return index.min(self.byte_len());
+        }
+        self.root.byte_index(index)
+    }
+
+    pub fn grapheme_index(&self, byte_index: usize) -> usize {
+        if self.is_ascii() {
+            return byte_index.min(self.len());
+        }
+        self.root.grapheme_index(byte_index)
+    }
+
+    //calls visit with the index, starting column, width and text of every
+    //grapheme from the one at `from` until it returns true, and returns the
+    //column it stopped at; whole chunks without tabs are skipped by their width
+    pub fn walk<F>(&self, tab_width: usize, from: Seek, mut visit: F) -> usize
+    where
+        F: FnMut(usize, usize, usize, &str) -> bool,
+    {
+        let tab_width = tab_width.max(1);
+        let (start, mut column) = match self.root.seek(from, tab_width, 0, 0) {
+            ControlFlow::Break(start) => start,
+            ControlFlow::Continue((_, end)) => return end,
+        };
+        self.root.each_from(start, 0, &mut |chunk_start, chunk| {
+            let mut step = |i: usize, grapheme: &str| {
+                let width = grapheme_width(grapheme, column, tab_width);
+                if visit(chunk_start.saturating_add(i), column, width, grapheme) {
+                    return true;
+                }
+                column = column.saturating_add(width);
+                false
+            };
+            if chunk.is_ascii() {
+                (0..chunk.len()).any(|i| step(i, chunk.get(i..=i).unwrap_or_default()))
+            } else {
+                chunk.graphemes(true).enumerate().any(|(i, grapheme)| step(i, grapheme))
+            }
+        });
+        column
+    }
+}
+
+fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        tab_width.saturating_sub(column.checked_rem(tab_width).unwrap_or(0))
+    } else {
+        width(grapheme)
+    }
+}
+
+//the column after the text when it starts at `column`
+fn end_column(text: &str, column: usize, tab_width: usize) -> usize {
+    text.graphemes(true)
+        .fold(column, |column, grapheme| column.saturating_add(grapheme_width(grapheme, column, tab_width)))
+}
+
+//the byte offset of the grapheme at index within a chunk
+fn chunk_byte_index(text: &str, summary: Summary, index: usize) -> usize {
+    if summary.bytes == summary.graphemes {
+        return index.min(text.len());
+    }
+    text.grapheme_indices(true).nth(index).map_or(text.len(), |(byte, _)| byte)
+}
+
+//cuts the text between graphemes into chunks half the maximum size,
+//leaving room for typing before they have to be split again
+fn chunks(text: &str) -> Vec<String> {
+    let target = MAX_CHUNK / 2;
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let boundaries: Box<dyn Iterator<Item = usize>> = if text.is_ascii() {
+        Box::new(0..text.len())
+    } else {
+        Box::new(text.grapheme_indices(true).map(|(byte, _)| byte))
+    };
+    for byte in boundaries {
+        if byte.saturating_sub(start) >= target {
+            chunks.push(text.get(start..byte).unwrap_or_default().to_string());
+            start = byte;
+        }
+    }
+    chunks.push(text.get(start..).unwrap_or_default().to_string());
+    chunks
+}
+
+fn group(nodes: Vec<Rc<Node>>) -> Vec<Vec<Rc<Node>>> {
+    if nodes.len() <= MAX_CHILDREN {
+        return vec![nodes];
+    }
+    let mut groups = Vec::new();
+    let mut nodes = nodes.into_iter().peekable();
+    while nodes.peek().is_some() {
+        groups.push(nodes.by_ref().take(MAX_CHILDREN / 2).collect());
+    }
+    groups
+}
+
+impl Node {
+    fn leaf(text: String) -> Self {
+        Self {
+            summary: Summary::of(&text),
+            kind: Kind::Leaf(text),
+        }
+    }
+
+    fn branch(children: Vec<Rc<Self>>) -> Self {
+        Self {
+            summary: sum(&children),
+            kind: Kind::Branch(children),
+        }
+    }
+
+    //returns the nodes split off because this one grew too big, they go right after it
+    #[allow(clippy::arithmetic_side_effects)]
+    fn insert(&mut self, index: usize, text: &str) -> Vec<Rc<Self>> {
+        let summary = self.summary;
+        match &mut self.kind {
+            Kind::Leaf(string) => {
+                let byte = chunk_byte_index(string, summary, index);
+                string.insert_str(byte, text);
+                if string.len() <= MAX_CHUNK {
+                    self.summary = Summary::of(string);
+                    return Vec::new();
+                }
+                let mut pieces = chunks(string).into_iter().map(Self::leaf);
+                let first = pieces.next().unwrap_or_else(|| Self::leaf(String::new()));
+                let rest = pieces.map(Rc::new).collect();
+                *self = first;
+                rest
+            }
+            Kind::Branch(children) => {
+                //an index between two children goes to the end of the first
+                let mut offset = 0;
+                let mut child_index = children.len().saturating_sub(1);
+                for (i, child) in children.iter().enumerate() {
+                    if index <= offset + child.summary.graphemes {
+                        child_index = i;
+                        break;
+                    }
+                    offset += child.summary.graphemes;
+                }
+                let Some(child) = children.get_mut(child_index) else {
+                    return Vec::new();
+                };
+                let extra = Rc::make_mut(child).insert(index - offset, text);
+                let at = child_index + 1;
+                children.splice(at..at, extra);
+                let mut groups = group(std::mem::take(children)).into_iter();
+                *children = groups.next().unwrap_or_default();
+                self.summary = sum(children);
+                groups.map(|group| Rc::new(Self::branch(group))).collect()
+            }
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn remove(&mut self, start: usize, end: usize, removed: &mut String) {
+        let summary = self.summary;
+        match &mut self.kind {
+            Kind::Leaf(string) => {
+                let start = chunk_byte_index(string, summary, start);
+                let end = chunk_byte_index(string, summary, end);
+                removed.push_str(string.get(start..end).unwrap_or_default());
+                string.replace_range(start..end, "");
+                self.summary = Summary::of(string);
+            }
+            Kind::Branch(children) => {
+                let mut offset = 0;
+                children.retain_mut(|child| {
+                    let child_start = offset;
+                    let child_end = offset + child.summary.graphemes;
+                    offset = child_end;
+                    if child_end <= start || child_start >= end {
+                        return true;
+                    }
+                    if start <= child_start && child_end <= end {
+                        child.slice(0, child_end - child_start, removed);
+                        return false;
+                    }
+                    let child = Rc::make_mut(child);
+                    child.remove(start.max(child_start) - child_start, end.min(child_end) - child_start, removed);
+                    child.summary.bytes > 0
+                });
+                self.summary = sum(children);
+            }
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn slice(&self, start: usize, end: usize, text: &mut String) {
+        match &self.kind {
+            Kind::Leaf(string) => {
+                let from = chunk_byte_index(string, self.summary, start);
+                let to = chunk_byte_index(string, self.summary, end);
+                text.push_str(string.get(from..to).unwrap_or_default());
+            }
+            Kind::Branch(children) => {
+                let mut offset = 0;
+                for child in children {
+                    let child_end = offset + child.summary.graphemes;
+                    if child_end > start && offset < end {
+                        child.slice(start.saturating_sub(offset), end.min(child_end) - offset, text);
+                    }
+                    offset = child_end;
+                    if offset >= end {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect_chunks<'a>(&'a self, chunks: &mut Vec<&'a str>) {
+        match &self.kind {
+            Kind::Leaf(string) => chunks.push(string),
+            Kind::Branch(children) => {
+                for child in children {
+                    child.collect_chunks(chunks);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn byte_index(&self, index: usize) -> usize {
+        match &self.kind {
+            Kind::Leaf(string) => chunk_byte_index(string, self.summary, index),
+            Kind::Branch(children) => {
+                let (mut graphemes, mut bytes) = (0, 0);
+                for child in children {
+                    if index < graphemes + child.summary.graphemes {
+                        return bytes + child.byte_index(index - graphemes);
+                    }
+                    graphemes += child.summary.graphemes;
+                    bytes += child.summary.bytes;
+                }
+                bytes
+            }
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn grapheme_index(&self, byte_index: usize) -> usize {
+        match &self.kind {
+            Kind::Leaf(string) => string
+                .grapheme_indices(true)
+                .take_while(|(byte, _)| *byte < byte_index)
+                .count(),
+            Kind::Branch(children) => {
+                let (mut graphemes, mut bytes) = (0, 0);
+                for child in children {
+                    if byte_index < bytes + child.summary.bytes {
+                        return graphemes + child.grapheme_index(byte_index - bytes);
+                    }
+                    graphemes += child.summary.graphemes;
+                    bytes += child.summary.bytes;
+                }
+                graphemes
+            }
+        }
+    }
+
+    //the index and column of the start of the chunk holding the target,
+    //or those of the end of this node when it isn't in it
+    #[allow(clippy::arithmetic_side_effects)]
+    fn seek(&self, target: Seek, tab_width: usize, index: usize, column: usize) -> ControlFlow<(usize, usize), (usize, usize)> {
+        let Summary {graphemes, width, tabs, ..} = self.summary;
+        let end_index = index + graphemes;
+        //without tabs the width doesn't depend on where the node starts
+        if !tabs {
+            let inside = match target {
+                Seek::Index(target) => target < end_index,
+                Seek::Column(target) => target < column + width,
+            };
+            if !inside {
+                return ControlFlow::Continue((end_index, column + width));
+            }
+        }
+        match &self.kind {
+            Kind::Leaf(string) => {
+                let end_column = if tabs { end_column(string, column, tab_width) } else { column + width };
+                let inside = match target {
+                    Seek::Index(target) => target < end_index,
+                    Seek::Column(target) => target < end_column,
+                };
+                if inside {
+                    ControlFlow::Break((index, column))
+                } else {
+                    ControlFlow::Continue((end_index, end_column))
+                }
+            }
+            Kind::Branch(children) => {
+                let mut position = (index, column);
+                for child in children {
+                    position = child.seek(target, tab_width, position.0, position.1)?;
+                }
+                ControlFlow::Continue(position)
+            }
+        }
+    }
+
+    //calls visit with every chunk from the one starting at `from` and the
+    //index it starts at, until it returns true
+    #[allow(clippy::arithmetic_side_effects)]
+    fn each_from<F>(&self, from: usize, index: usize, visit: &mut F) -> bool
+    where
+        F: FnMut(usize, &str) -> bool,
+    {
+        match &self.kind {
+            Kind::Leaf(string) => index >= from && visit(index, string),
+            Kind::Branch(children) => {
+                let mut offset = index;
+                for child in children {
+                    let end = offset + child.summary.graphemes;
+                    if end > from && child.each_from(from, offset, visit) {
+                        return true;
+                    }
+                    offset = end;
+                }
+                false
+            }
+        }
+    }
+}
+
+fn sum(children: &[Rc<Node>]) -> Summary {
+    children.iter().fold(Summary::default(), |summary, child| summary.add(child.summary))
+}
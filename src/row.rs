@@ -1,163 +1,536 @@
 use std::cmp;
 use unicode_segmentation::UnicodeSegmentation;
+use crate::editor::SearchDirection;
+use crate::document::LineEnding;
+use crate::filetype::HighlightingOptions;
+use crate::highlighting;
+use crate::rope::{Rope, Seek};
+use regex::Regex;
 
-#[derive(Default)]
+//rows longer than this are not highlighted, to keep editing huge lines responsive
+const MAX_HIGHLIGHT_LEN: usize = 10_000;
+
+//how many graphemes around the cursor word lookups start with, doubled until the word fits
+const WORD_WINDOW: usize = 64;
+
+#[derive(Default, Clone)]
 pub struct Row {
-    string: String,
-    len: usize,
+    text: Rope,
+    highlighting: Vec<highlighting::Type>,
+    is_highlighted: bool,
+    starts_in_comment: bool,
+    ends_in_comment: bool,
+    match_range: Option<(usize, usize)>,
+    ending: LineEnding,
 }
 
 impl From<&str> for Row {
     fn from(slice: &str) ->Self {
         Self { 
-            string: String::from(slice),
-            len: slice.graphemes(true).count(),
+            text: Rope::from(slice),
+            ..Self::default()
         }
     }
 }
 
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.string.len());
-        let start = cmp::min(start, end);
-        let mut result = String::new();
-        #[allow(clippy::arithmetic_side_effects)]
-        for grapheme in self.string
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-        {
-            //replaces tabs with a space
-            if grapheme == "\t" {
-                result.push_str(" ");
+    //returns what is drawn in the screen columns start..end, grouped into runs
+    //of the same highlight type, tabs are expanded to spaces and wide graphemes
+    //cut by either edge are replaced with spaces
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn render(&self, start: usize, end: usize, tab_width: usize) -> Vec<(highlighting::Type, String)> {
+        let mut result: Vec<(highlighting::Type, String)> = Vec::new();
+        self.text.walk(tab_width, Seek::Column(start), |index, column, width, grapheme| {
+            if column >= end {
+                return true;
+            }
+            //zero width graphemes are kept with the grapheme before them
+            if (column + width <= start && width > 0) || (column < start && width == 0) {
+                return false;
+            }
+            let visible = cmp::min(column + width, end) - cmp::max(column, start);
+            let text = if grapheme == "\t" || visible < width {
+                " ".repeat(visible)
             } else {
-                result.push_str(grapheme);
+                grapheme.to_string()
+            };
+            let highlighting_type = self
+                .highlighting
+                .get(index)
+                .copied()
+                .unwrap_or(highlighting::Type::None);
+            match result.last_mut() {
+                Some((current, run)) if *current == highlighting_type => run.push_str(&text),
+                _ => result.push((highlighting_type, text)),
             }
-        }
+            false
+        });
         result
     }
 
-    pub fn insert(&mut self, index: usize, c: char) {
-        if index >= self.len() {
-            self.string.push(c);
-            self.len += 1;
-            return;
-        }
-        let mut result: String = String::new();
-        let mut length = 0;
-        for (i, grapheme) in self.string[..].graphemes(true).enumerate() {
-            length += 1;
-            if i == index {
-                length += 1;
-                result.push(c)
+    //the screen column where the grapheme at index starts
+    pub fn column(&self, index: usize, tab_width: usize) -> usize {
+        let mut result = None;
+        let end = self.text.walk(tab_width, Seek::Index(index), |current, column, _, _| {
+            if current == index {
+                result = Some(column);
             }
-            result.push_str(grapheme);
-        }
-        self.len = length;
-        self.string = result;
+            result.is_some()
+        });
+        result.unwrap_or(end)
+    }
+
+    //the grapheme drawn at the given screen column, columns past the end
+    //of the row map to its end
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn index_at_column(&self, column: usize, tab_width: usize) -> usize {
+        let mut result = None;
+        self.text.walk(tab_width, Seek::Column(column), |index, start, width, _| {
+            if start + width > column {
+                result = Some(index);
+            }
+            result.is_some()
+        });
+        result.unwrap_or_else(|| self.len())
     }
 
+    //where each screen line starts when the row is wrapped at width columns, as
+    //grapheme index and column, lines break after whitespace when possible and
+    //a full last line is followed by an empty one for the cursor
     #[allow(clippy::arithmetic_side_effects)]
-    pub fn delete(&mut self, index: usize) {
-        if index >= self.len() {
-            return;
-        } 
-        let mut result: String = String::new();
-        let mut length = 0;
-        for (i, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if i != index {
-                length += 1;
-                result.push_str(grapheme)
+    pub fn wrap(&self, width: usize, tab_width: usize) -> Vec<(usize, usize)> {
+        let width = width.max(1);
+        let mut lines = vec![(0, 0)];
+        let mut line_start = 0;
+        let mut last_break: Option<(usize, usize)> = None;
+        let end = self.text.walk(tab_width, Seek::Index(0), |index, column, grapheme_width, grapheme| {
+            if column + grapheme_width - line_start > width && column > line_start {
+                let (index, column) = match last_break {
+                    Some(position) if position.1 > line_start => position,
+                    _ => (index, column),
+                };
+                lines.push((index, column));
+                line_start = column;
+                last_break = None;
+            }
+            //graphemes after the break may still not fit, like a wide tab
+            if column + grapheme_width - line_start > width && column > line_start {
+                lines.push((index, column));
+                line_start = column;
             }
+            if grapheme.chars().all(char::is_whitespace) {
+                last_break = Some((index + 1, column + grapheme_width));
+            }
+            false
+        });
+        if end - line_start >= width {
+            lines.push((self.len(), end));
         }
-        self.len = length;
-        self.string = result;
+        lines
+    }
+
+    //the text of the graphemes in start..end
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        self.text.slice(start, end)
+    }
+
+    pub fn insert(&mut self, index: usize, text: &str) {
+        self.splice(index, index, text);
+    }
+
+    //removes the graphemes in start..end and returns them
+    pub fn delete(&mut self, start: usize, end: usize) -> String {
+        self.splice(start, end, "")
     }
 
     pub fn split(&mut self, index: usize) -> Self {
-        let mut row: String = String::new();
-        let mut length = 0;
-        let mut splitted_row: String = String::new();
-        let mut splitted_length = 0;
-
-        for (i, grapheme) in self.string.graphemes(true).enumerate() {
-            if i < index {
-                length += 1;
-                row.push_str(grapheme)
-            } else {
-                splitted_length += 1;
-                splitted_row.push_str(grapheme);
-            }
+        let tail = self.text.remove(index, self.len());
+        self.is_highlighted = false;
+        Self {
+            text: Rope::from(tail.as_str()),
+            ending: self.ending,
+            ..Self::default()
         }
+    }
 
-        self.string = row;
-        self.len = length;
+    //replaces the graphemes in start..end with text and returns the removed text,
+    //only the graphemes around the edit are segmented again
+    #[allow(clippy::arithmetic_side_effects)]
+    fn splice(&mut self, start: usize, end: usize, text: &str) -> String {
+        self.is_highlighted = false;
+        let len = self.len();
+        let start = cmp::min(start, len);
+        let end = end.clamp(start, len);
 
-        Self { string: splitted_row, len: splitted_length }
+        if self.text.is_ascii() && text.is_ascii() {
+            let removed = self.text.remove(start, end);
+            self.text.insert(start, text);
+            return removed;
+        }
+
+        //the graphemes next to the edit can merge with the inserted text
+        let segment_start = start.saturating_sub(1);
+        let after = self.text.remove(end, cmp::min(end + 1, len));
+        let removed = self.text.remove(start, end);
+        let before = self.text.remove(segment_start, start);
+        self.text.insert(segment_start, &format!("{before}{text}{after}"));
+        removed
     }
 
     pub fn is_alphanumeric(&self, index: usize) -> bool {
-        self.string.chars().nth(index).unwrap().is_alphanumeric()
+        self.slice(index, index.saturating_add(1)).chars().next().unwrap().is_alphanumeric()
     }
 
+    //the text before index is copied in growing windows, so long rows are not copied whole
     pub fn distance_to_start(&self, index: usize) -> usize {
-        let mut iter = self.string.chars().rev().skip(self.len - index).peekable();
-        let mut i = 0;
+        let mut window = WORD_WINDOW;
+        loop {
+            let start = index.saturating_sub(window);
+            let (distance, done) = word_distance(self.slice(start, index).chars().rev());
+            if done || start == 0 {
+                return distance;
+            }
+            window = window.saturating_mul(2);
+        }
+    }
 
-        while let Some(c) = iter.peek() {
-            if c.is_alphanumeric() {
-                break;
+    pub fn distance_to_end(&self, index: usize) -> usize {
+        let mut window = WORD_WINDOW;
+        loop {
+            let end = cmp::min(index.saturating_add(window), self.len());
+            let (distance, done) = word_distance(self.slice(index, end).chars());
+            if done || end == self.len() {
+                return distance;
             }
-            i += 1;
-            iter.next();
+            window = window.saturating_mul(2);
         }
+    }
 
-        while let Some(c) = iter.peek() {
-            if !c.is_alphanumeric() {
-                break;
+    //returns the grapheme index of the first match at or after `at`,
+    //or of the last match before `at` when searching backward
+    #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
+        if at > self.len() || query.is_empty() {
+            return None;
+        }
+        let (start, end) = match direction {
+            SearchDirection::Forward => (at, self.len()),
+            SearchDirection::Backward => (0, at),
+        };
+        let substring = self.slice(start, end);
+        let matching_byte_index = match direction {
+            SearchDirection::Forward => substring.find(query),
+            SearchDirection::Backward => substring.rfind(query),
+        }?;
+        Some(start + substring[..matching_byte_index].graphemes(true).count())
+    }
+
+    //returns the grapheme range of the first non-empty regex match at or after `at`
+    pub fn find_regex(&self, regex: &Regex, at: usize) -> Option<(usize, usize)> {
+        let string = self.contents();
+        let mut byte_index = self.text.byte_index(at);
+        while let Some(found) = regex.find_at(&string, byte_index) {
+            if found.start() < found.end() {
+                return Some((
+                    self.text.grapheme_index(found.start()),
+                    self.text.grapheme_index(found.end()),
+                ));
             }
-            i += 1;
-            iter.next();
+            byte_index = string
+                .get(found.end()..)
+                .and_then(|rest| rest.chars().next())
+                .map(|c| found.end().saturating_add(c.len_utf8()))?;
         }
-        i
+        None
     }
 
-    pub fn distance_to_end(&self, index: usize) -> usize {
-        let mut iter = self.string.chars().skip(index).peekable();
-        let mut i = 0;
+    //expands capture group references in `replacement` for the match starting at `at`
+    pub fn expand_regex(&self, regex: &Regex, at: usize, replacement: &str) -> String {
+        let mut result = String::new();
+        if let Some(captures) = regex.captures_at(&self.contents(), self.text.byte_index(at)) {
+            captures.expand(replacement, &mut result);
+        }
+        result
+    }
 
-        while let Some(c) = iter.peek() {
-            if c.is_alphanumeric() {
-                break;
+    //the word around index, or the run of separators if index is not on a word,
+    //looked up in a window around index that grows until the whole word is in it
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn word_at(&self, index: usize) -> (usize, usize) {
+        let len = self.len();
+        if len == 0 {
+            return (0, 0);
+        }
+        let index = cmp::min(index, len - 1);
+        let mut window = WORD_WINDOW;
+        loop {
+            let start = index.saturating_sub(window);
+            let end = cmp::min(index.saturating_add(window), len);
+            let text = self.slice(start, end);
+            let graphemes: Vec<&str> = text.graphemes(true).collect();
+            let at = index - start;
+            let separator = graphemes.get(at).map_or(true, |grapheme| is_separator(grapheme));
+            let word_start = graphemes
+                .get(..at)
+                .unwrap_or_default()
+                .iter()
+                .rposition(|grapheme| is_separator(grapheme) != separator)
+                .map(|position| start + position + 1);
+            let word_end = graphemes
+                .get(at..)
+                .unwrap_or_default()
+                .iter()
+                .position(|grapheme| is_separator(grapheme) != separator)
+                .map(|position| index + position);
+            if (word_start.is_some() || start == 0) && (word_end.is_some() || end == len) {
+                return (word_start.unwrap_or(0), word_end.unwrap_or(len));
             }
-            i += 1;
-            iter.next();
+            window = window.saturating_mul(2);
+        }
+    }
+
+    //highlights the row and returns whether it ends inside a multiline comment,
+    //rows are only recomputed when they or the state they start in changed
+    #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+    pub fn highlight(
+        &mut self,
+        opts: &HighlightingOptions,
+        match_range: Option<(usize, usize)>,
+        start_with_comment: bool,
+    ) -> bool {
+        if self.is_highlighted
+            && self.starts_in_comment == start_with_comment
+            && self.match_range == match_range
+        {
+            return self.ends_in_comment;
+        }
+
+        if self.len() > MAX_HIGHLIGHT_LEN {
+            self.highlighting = Vec::new();
+            self.is_highlighted = true;
+            self.starts_in_comment = start_with_comment;
+            self.ends_in_comment = start_with_comment;
+            self.match_range = match_range;
+            return start_with_comment;
         }
 
-        while let Some(c) = iter.peek() {
-            if !c.is_alphanumeric() {
-                break;
+        let string = self.contents();
+        let graphemes: Vec<(usize, &str)> = string.grapheme_indices(true).collect();
+        let mut highlighting = Vec::with_capacity(graphemes.len());
+        let mut in_comment = start_with_comment;
+        let mut index = 0;
+
+        if opts.headings() && !in_comment && string.starts_with('#') {
+            highlighting.resize(graphemes.len(), highlighting::Type::PrimaryKeywords);
+            index = graphemes.len();
+        }
+
+        while let Some(&(byte_index, grapheme)) = graphemes.get(index) {
+            let rest = &string[byte_index..];
+
+            if let Some((start, end)) = opts.multiline_comment() {
+                let marker = if in_comment { end } else { start };
+                if rest.starts_with(marker) {
+                    let len = marker.graphemes(true).count();
+                    highlighting.extend((0..len).map(|_| highlighting::Type::MultilineComment));
+                    index += len;
+                    in_comment = !in_comment;
+                    continue;
+                }
+                if in_comment {
+                    highlighting.push(highlighting::Type::MultilineComment);
+                    index += 1;
+                    continue;
+                }
+            }
+
+            if let Some(comment) = opts.comment() {
+                if rest.starts_with(comment) {
+                    highlighting.resize(graphemes.len(), highlighting::Type::Comment);
+                    break;
+                }
+            }
+
+            let previous_is_separator = index == 0
+                || graphemes.get(index - 1).map_or(true, |(_, g)| is_separator(g));
+            let previous_type = highlighting.last().copied();
+
+            if let Some(delimiter) = opts.strings().iter().find(|c| grapheme.starts_with(**c)) {
+                let len = string_length(&graphemes[index..], *delimiter);
+                highlighting.extend((0..len).map(|_| highlighting::Type::String));
+                index += len;
+                continue;
+            }
+
+            if opts.characters() && grapheme == "'" {
+                if let Some(len) = character_length(&graphemes[index..]) {
+                    highlighting.extend((0..len).map(|_| highlighting::Type::Character));
+                    index += len;
+                    continue;
+                }
+            }
+
+            if opts.numbers() {
+                let is_digit = grapheme.chars().all(|c| c.is_ascii_digit());
+                let continues_number = previous_type == Some(highlighting::Type::Number);
+                if (is_digit && (previous_is_separator || continues_number))
+                    || (grapheme == "." && continues_number)
+                {
+                    highlighting.push(highlighting::Type::Number);
+                    index += 1;
+                    continue;
+                }
+            }
+
+            if previous_is_separator {
+                let keyword = opts
+                    .primary_keywords()
+                    .iter()
+                    .map(|keyword| (keyword, highlighting::Type::PrimaryKeywords))
+                    .chain(
+                        opts.secondary_keywords()
+                            .iter()
+                            .map(|keyword| (keyword, highlighting::Type::SecondaryKeywords)),
+                    )
+                    .find(|(keyword, _)| {
+                        rest.strip_prefix(**keyword).map_or(false, |after| {
+                            after.graphemes(true).next().map_or(true, is_separator)
+                        })
+                    });
+                if let Some((keyword, highlighting_type)) = keyword {
+                    let len = keyword.graphemes(true).count();
+                    highlighting.extend((0..len).map(|_| highlighting_type));
+                    index += len;
+                    continue;
+                }
             }
-            i += 1;
-            iter.next();
+
+            highlighting.push(highlighting::Type::None);
+            index += 1;
+        }
+
+        highlighting.truncate(graphemes.len());
+        if let Some((start, end)) = match_range {
+            for highlighting_type in highlighting.iter_mut().take(end).skip(start) {
+                *highlighting_type = highlighting::Type::Match;
+            }
+        }
+
+        self.highlighting = highlighting;
+        self.is_highlighted = true;
+        self.starts_in_comment = start_with_comment;
+        self.ends_in_comment = in_comment;
+        self.match_range = match_range;
+        in_comment
+    }
+
+    //the line ending written after this row
+    pub fn ending(&self) -> LineEnding {
+        self.ending
+    }
+
+    pub fn set_ending(&mut self, ending: LineEnding) {
+        self.ending = ending;
+    }
+
+    pub fn unhighlight(&mut self) {
+        self.is_highlighted = false;
+    }
+
+    //whether the highlighting is still right for a row starting in this comment state
+    pub fn is_highlighted_for(&self, start_with_comment: bool) -> bool {
+        self.is_highlighted && self.starts_in_comment == start_with_comment
+    }
+
+    pub fn ends_in_comment(&self) -> bool {
+        self.ends_in_comment
+    }
+
+    //recolours the search match of an already highlighted row, which can't
+    //change the comment state it ends in
+    pub fn set_match(&mut self, opts: &HighlightingOptions, match_range: Option<(usize, usize)>) {
+        if self.is_highlighted {
+            self.highlight(opts, match_range, self.starts_in_comment);
         }
-        i
     }
 
-    pub fn as_bytes(&self) -> &[u8] {
-        self.string.as_bytes()
+    //the text in pieces, for writing it out without copying
+    pub fn chunks(&self) -> Vec<&str> {
+        self.text.chunks()
+    }
+
+    pub fn contents(&self) -> String {
+        self.text.chunks().concat()
     }
 
     pub fn append(&mut self, other: &Self) {
-        self.string.push_str(&other.string);
-        self.len += other.len;
+        self.splice(self.len(), self.len(), &other.contents());
+        self.ending = other.ending;
     }
 
     pub fn len(&self) -> usize {
-        self.len
+        self.text.len()
+    }
+
+    //the byte offset of the grapheme at index
+    pub fn byte_index(&self, index: usize) -> usize {
+        self.text.byte_index(index)
+    }
+
+    //the number of graphemes that start before the byte offset
+    pub fn grapheme_index(&self, byte_index: usize) -> usize {
+        self.text.grapheme_index(byte_index)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.len() == 0
+    }
+}
+
+//counts the characters up to the end of the next word, and whether
+//something comes after it
+fn word_distance(chars: impl Iterator<Item = char>) -> (usize, bool) {
+    let mut chars = chars.peekable();
+    let mut distance: usize = 0;
+    while chars.next_if(|c| !c.is_alphanumeric()).is_some() {
+        distance = distance.saturating_add(1);
+    }
+    while chars.next_if(|c| c.is_alphanumeric()).is_some() {
+        distance = distance.saturating_add(1);
+    }
+    (distance, chars.peek().is_some())
+}
+
+fn is_separator(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .map_or(true, |c| c.is_whitespace() || (c.is_ascii_punctuation() && c != '_'))
+}
+
+//length in graphemes of a string literal starting at the first grapheme,
+//unterminated strings run to the end of the row
+#[allow(clippy::arithmetic_side_effects)]
+fn string_length(graphemes: &[(usize, &str)], delimiter: char) -> usize {
+    let mut escaped = false;
+    for (i, (_, grapheme)) in graphemes.iter().enumerate().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if *grapheme == "\\" {
+            escaped = true;
+        } else if grapheme.starts_with(delimiter) {
+            return i + 1;
+        }
+    }
+    graphemes.len()
+}
+
+//length in graphemes of a character literal like 'a' or '\n' starting at the first grapheme
+fn character_length(graphemes: &[(usize, &str)]) -> Option<usize> {
+    let closing = if graphemes.get(1)?.1 == "\\" { 3 } else { 2 };
+    if graphemes.get(closing)?.1 == "'" {
+        Some(closing.saturating_add(1))
+    } else {
+        None
     }
 }
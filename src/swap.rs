@@ -0,0 +1,111 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+//swap files hold the unsaved contents of a document next to the file,
+//the header records which kibi process wrote it, they are named apart
+//from vim's `.name.swp` so the two editors never touch each other's files
+const HEADER: &str = "kibi swap file";
+
+pub fn path(filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{name}.kibi.swp"))
+}
+
+//files that were not written by kibi, or that another running kibi
+//is still using, are never overwritten
+pub fn create(path: &Path) -> Result<BufWriter<fs::File>, io::Error> {
+    if path.exists() {
+        match read_pid(path) {
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "not a kibi swap file",
+                ))
+            }
+            Some(pid) if pid != process::id() && is_running(pid) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "swap file in use by another kibi",
+                ))
+            }
+            Some(_) => (),
+        }
+    }
+    let mut writer = BufWriter::new(open_private(path)?);
+    writeln!(writer, "{HEADER} {}", process::id())?;
+    Ok(writer)
+}
+
+//the unsaved text may come from a file others can't read, so only the
+//owner can read the swap file, even one left behind with wider permissions
+#[cfg(unix)]
+fn open_private(path: &Path) -> Result<fs::File, io::Error> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> Result<fs::File, io::Error> {
+    fs::File::create(path)
+}
+
+//returns the saved contents if the swap file was left behind by a kibi
+//that is no longer running
+pub fn read_stale(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    let (header, text) = contents.split_once('\n')?;
+    let pid = parse_header(header)?;
+    if pid != process::id() && is_running(pid) {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+//removes the swap file, unless something else than kibi wrote it
+pub fn remove(path: &Path) {
+    if read_pid(path).is_some() {
+        _ = fs::remove_file(path);
+    }
+}
+
+//the process that wrote the swap file, None if it isn't a kibi swap file
+fn read_pid(path: &Path) -> Option<u32> {
+    let mut header = String::new();
+    BufReader::new(fs::File::open(path).ok()?).read_line(&mut header).ok()?;
+    parse_header(header.trim_end())
+}
+
+fn parse_header(header: &str) -> Option<u32> {
+    header.strip_prefix(HEADER)?.trim().parse().ok()
+}
+
+//signal 0 only checks whether the process exists, a permission error
+//means it exists but belongs to another user
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    //SAFETY: kill with signal 0 sends nothing and has no memory effects
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+//without a way to check, every swap file is treated as in use so another
+//instance's swap file is never discarded
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
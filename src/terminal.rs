@@ -1,16 +1,26 @@
 use std::io::{self, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
 use crate::editor::Position;
+use base64::engine::{general_purpose, Engine};
 use crossterm::{terminal::{self, ClearType},
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     execute, cursor, style::{self, Color,}};
 
+//set while the terminal is in raw mode on the alternate screen
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HANDLERS: Once = Once::new();
+
 pub struct Size {
     pub width: u16,
     pub height: u16
 }
 
+//owns the terminal state, which is restored when it is dropped,
+//when kibi panics and when it is killed by SIGTERM or SIGHUP
 pub struct Terminal {
     size: Size,
-    _stdout: io::Result<()>,
 }
 
 impl Terminal {
@@ -18,21 +28,53 @@ impl Terminal {
         let width = terminal::size()?.0;
         let height = terminal::size()?.1;
 
+        install_handlers();
+        terminal::enable_raw_mode()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        execute!(
+            io::stdout(),
+            terminal::EnterAlternateScreen,
+            EnableBracketedPaste,
+            EnableMouseCapture
+        )?;
+
         Ok(
             Self {size: Size {
                 width: width,
                 height: height.saturating_sub(2),
             },
-            //bad error handling
-            _stdout: terminal::enable_raw_mode(),
         }
         )
     }
 
+    //puts the terminal back into the state it was in before kibi started,
+    //only the first call does anything
+    pub fn restore() {
+        if ACTIVE.swap(false, Ordering::SeqCst) {
+            _ = execute!(
+                io::stdout(),
+                style::ResetColor,
+                cursor::Show,
+                DisableBracketedPaste,
+                DisableMouseCapture,
+                terminal::LeaveAlternateScreen
+            );
+            _ = terminal::disable_raw_mode();
+        }
+    }
+
     pub fn size(&self) -> &Size {
         &self.size
     }
 
+    //the last two lines are reserved for the status and message bars
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.size = Size {
+            width,
+            height: height.saturating_sub(2),
+        };
+    }
+
     pub fn clear_screen() {
         //bad error handling
         execute!(io::stdout(), terminal::Clear(ClearType::All)).unwrap();
@@ -54,6 +96,14 @@ impl Terminal {
         io::stdout().flush()
     }
 
+    //sets the clipboard of the terminal emulator with OSC 52,
+    //terminals that don't support it ignore the sequence
+    pub fn set_clipboard(text: &str) -> Result<(), std::io::Error> {
+        let encoded = general_purpose::STANDARD.encode(text);
+        print!("\x1b]52;c;{encoded}\x07");
+        Self::flush()
+    }
+
     pub fn reset_color() -> Result<(), std::io::Error> {
         execute!(io::stdout(), style::ResetColor)
     }
@@ -65,4 +115,41 @@ impl Terminal {
     pub fn set_fg_color(color: Color) -> Result<(), std::io::Error> {
         execute!(io::stdout(), style::SetForegroundColor(color))
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
+
+//the panic message is printed by the previous hook, after the terminal is restored
+fn install_handlers() {
+    HANDLERS.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            Terminal::restore();
+            previous(info);
+        }));
+        handle_signals();
+    });
+}
+
+#[cfg(unix)]
+fn handle_signals() {
+    use signal_hook::consts::{SIGHUP, SIGTERM};
+    use signal_hook::iterator::Signals;
+    use std::{process, thread};
+
+    if let Ok(mut signals) = Signals::new([SIGTERM, SIGHUP]) {
+        thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                Terminal::restore();
+                process::exit(signal.saturating_add(128));
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn handle_signals() {}
\ No newline at end of file
@@ -0,0 +1,491 @@
+//the vi-style modal layer: turns what is typed in normal and visual mode
+//into motions and operators, which the editor carries out with the
+//document's own operations
+
+use crate::editor::Position;
+use crate::{Document, Row};
+use crossterm::event::KeyEvent;
+use std::cmp;
+
+//counts are capped so a typo like 99999999j can't hang the editor
+const MAX_COUNT: usize = 100_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
+            Self::VisualLine => "V-LINE",
+        }
+    }
+
+    pub fn is_visual(self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    //gg and G go to the line given as count instead
+    FirstLine,
+    LastLine,
+    //f, t, F and T
+    Find { c: char, forward: bool, till: bool },
+    //the operator typed twice, like dd
+    Line,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Insert {
+    Before,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Motion),
+    //an operator typed in visual mode acts on the selection
+    OperateSelection(Operator),
+    Insert(Insert),
+    Paste { before: bool },
+    Undo,
+    Repeat,
+    Visual(Mode),
+    //o in visual mode moves the cursor to the other end of the selection
+    SwapAnchor,
+}
+
+pub enum Parsed {
+    Pending,
+    Invalid,
+    Done { action: Action, count: Option<usize> },
+}
+
+//the text an operator works on, lines are inclusive
+#[derive(Clone, Copy)]
+pub enum Range {
+    Chars(Position, Position),
+    Lines(usize, usize),
+}
+
+#[derive(Default)]
+pub struct Vi {
+    pub mode: Mode,
+    //what has been typed of the current command, like `2d3`
+    pending: String,
+    //the keys of the current command, kept in case it changes the document
+    pub keys: Vec<KeyEvent>,
+    last_find: Option<(char, bool, bool)>,
+    //the last deleted or yanked text, and whether it was whole lines
+    pub register: String,
+    pub linewise: bool,
+    //the keys of the change being made and of the last complete one, replayed by `.`
+    pub recording: Option<Vec<KeyEvent>>,
+    pub last_change: Vec<KeyEvent>,
+}
+
+impl Vi {
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+        self.keys.clear();
+    }
+
+    pub fn feed(&mut self, c: char) -> Parsed {
+        self.pending.push(c);
+        let parsed = self.parse();
+        if !matches!(parsed, Parsed::Pending) {
+            self.pending.clear();
+            if matches!(parsed, Parsed::Invalid) {
+                self.keys.clear();
+            }
+        }
+        parsed
+    }
+
+    fn parse(&mut self) -> Parsed {
+        let pending = self.pending.clone();
+        let mut chars = pending.chars().peekable();
+        let count = parse_count(&mut chars);
+        let Some(c) = chars.next() else {
+            return Parsed::Pending;
+        };
+        let done = |action| Parsed::Done { action, count };
+        let operator = match c {
+            'd' => Some(Operator::Delete),
+            'c' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            _ => None,
+        };
+        if self.mode.is_visual() {
+            let action = match c {
+                'd' | 'x' => Action::OperateSelection(Operator::Delete),
+                'c' | 's' => Action::OperateSelection(Operator::Change),
+                'y' => Action::OperateSelection(Operator::Yank),
+                'o' => Action::SwapAnchor,
+                'v' => Action::Visual(Mode::Visual),
+                'V' => Action::Visual(Mode::VisualLine),
+                _ => return self.parse_motion(c, &mut chars, count, Action::Move),
+            };
+            return done(action);
+        }
+        if let Some(operator) = operator {
+            let inner = parse_count(&mut chars);
+            let count = match (count, inner) {
+                (Some(a), Some(b)) => Some(cmp::min(a.saturating_mul(b), MAX_COUNT)),
+                (a, b) => a.or(b),
+            };
+            return match chars.next() {
+                None => Parsed::Pending,
+                Some(motion) if motion == c => Parsed::Done {
+                    action: Action::Operate(operator, Motion::Line),
+                    count,
+                },
+                Some(motion) => self.parse_motion(motion, &mut chars, count, |motion| {
+                    Action::Operate(operator, motion)
+                }),
+            };
+        }
+        let action = match c {
+            //the shorthands for an operator and a motion
+            'x' => Action::Operate(Operator::Delete, Motion::Right),
+            'X' => Action::Operate(Operator::Delete, Motion::Left),
+            'D' => Action::Operate(Operator::Delete, Motion::LineEnd),
+            'C' => Action::Operate(Operator::Change, Motion::LineEnd),
+            's' => Action::Operate(Operator::Change, Motion::Right),
+            'S' => Action::Operate(Operator::Change, Motion::Line),
+            'Y' => Action::Operate(Operator::Yank, Motion::Line),
+            'i' => Action::Insert(Insert::Before),
+            'a' => Action::Insert(Insert::After),
+            'I' => Action::Insert(Insert::LineStart),
+            'A' => Action::Insert(Insert::LineEnd),
+            'o' => Action::Insert(Insert::LineBelow),
+            'O' => Action::Insert(Insert::LineAbove),
+            'p' => Action::Paste { before: false },
+            'P' => Action::Paste { before: true },
+            'u' => Action::Undo,
+            '.' => Action::Repeat,
+            'v' => Action::Visual(Mode::Visual),
+            'V' => Action::Visual(Mode::VisualLine),
+            _ => return self.parse_motion(c, &mut chars, count, Action::Move),
+        };
+        done(action)
+    }
+
+    fn parse_motion<I, F>(&mut self, c: char, chars: &mut I, count: Option<usize>, action: F) -> Parsed
+    where
+        I: Iterator<Item = char>,
+        F: Fn(Motion) -> Action,
+    {
+        let motion = match c {
+            'h' => Motion::Left,
+            'l' | ' ' => Motion::Right,
+            'k' => Motion::Up,
+            'j' => Motion::Down,
+            'w' => Motion::WordForward,
+            'b' => Motion::WordBackward,
+            'e' => Motion::WordEnd,
+            '0' => Motion::LineStart,
+            '^' => Motion::FirstNonBlank,
+            '$' => Motion::LineEnd,
+            'G' => Motion::LastLine,
+            'g' => match chars.next() {
+                None => return Parsed::Pending,
+                Some('g') => Motion::FirstLine,
+                Some(_) => return Parsed::Invalid,
+            },
+            'f' | 't' | 'F' | 'T' => {
+                let Some(target) = chars.next() else {
+                    return Parsed::Pending;
+                };
+                let find = (target, c.is_lowercase(), matches!(c, 't' | 'T'));
+                self.last_find = Some(find);
+                Motion::Find { c: find.0, forward: find.1, till: find.2 }
+            }
+            //repeats the last f or t, the other way for `,`
+            ';' | ',' => {
+                let Some((target, forward, till)) = self.last_find else {
+                    return Parsed::Invalid;
+                };
+                Motion::Find { c: target, forward: forward == (c == ';'), till }
+            }
+            _ => return Parsed::Invalid,
+        };
+        Parsed::Done { action: action(motion), count }
+    }
+}
+
+//a count never starts with 0, which is a motion of its own
+fn parse_count<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<usize> {
+    if !matches!(chars.peek(), Some('1'..='9')) {
+        return None;
+    }
+    let mut count: usize = 0;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        count = cmp::min(count.saturating_mul(10).saturating_add(digit as usize), MAX_COUNT);
+    }
+    Some(count)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+//the end of a row counts as a blank, like the newline it stands for
+fn class(document: &Document, position: Position) -> Class {
+    let Some(c) = document
+        .row(position.y)
+        .and_then(|row| row.slice(position.x, position.x.saturating_add(1)).chars().next())
+    else {
+        return Class::Blank;
+    };
+    if c.is_whitespace() {
+        Class::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+fn row_len(document: &Document, y: usize) -> usize {
+    document.row(y).map_or(0, Row::len)
+}
+
+//the grapheme after the position, the end of a row is followed by the next row
+pub fn next(document: &Document, position: Position) -> Option<Position> {
+    let Position {x, y} = position;
+    if x < row_len(document, y) {
+        Some(Position {x: x.saturating_add(1), y})
+    } else if y.saturating_add(1) < document.len() {
+        Some(Position {x: 0, y: y.saturating_add(1)})
+    } else {
+        None
+    }
+}
+
+fn previous(document: &Document, position: Position) -> Option<Position> {
+    let Position {x, y} = position;
+    if x > 0 {
+        Some(Position {x: x.saturating_sub(1), y})
+    } else if y > 0 {
+        let y = y.saturating_sub(1);
+        Some(Position {x: row_len(document, y), y})
+    } else {
+        None
+    }
+}
+
+pub fn first_non_blank(document: &Document, y: usize) -> usize {
+    document.row(y).map_or(0, |row| {
+        (0..row.len())
+            .find(|x| !row.slice(*x, x.saturating_add(1)).chars().all(char::is_whitespace))
+            .unwrap_or(0)
+    })
+}
+
+fn word_forward(document: &Document, from: Position) -> Position {
+    let mut position = from;
+    let start = class(document, position);
+    if start != Class::Blank {
+        while let Some(next) = next(document, position) {
+            position = next;
+            if next.y != from.y || class(document, next) != start {
+                break;
+            }
+        }
+    }
+    //an empty line counts as a word of its own
+    while class(document, position) == Class::Blank
+        && !(position.y != from.y && row_len(document, position.y) == 0)
+    {
+        match next(document, position) {
+            Some(next) => position = next,
+            None => break,
+        }
+    }
+    position
+}
+
+fn word_backward(document: &Document, from: Position) -> Position {
+    let Some(mut position) = previous(document, from) else {
+        return from;
+    };
+    while class(document, position) == Class::Blank && row_len(document, position.y) > 0 {
+        match previous(document, position) {
+            Some(previous) => position = previous,
+            None => return position,
+        }
+    }
+    let start = class(document, position);
+    if start != Class::Blank {
+        while let Some(previous) = previous(document, position) {
+            if previous.y != position.y || class(document, previous) != start {
+                break;
+            }
+            position = previous;
+        }
+    }
+    position
+}
+
+fn word_end(document: &Document, from: Position) -> Position {
+    let Some(mut position) = next(document, from) else {
+        return from;
+    };
+    while class(document, position) == Class::Blank {
+        match next(document, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    let start = class(document, position);
+    while let Some(next) = next(document, position) {
+        if next.y != position.y || class(document, next) != start {
+            break;
+        }
+        position = next;
+    }
+    position
+}
+
+//the end of the word the position is in, which may be the position itself
+fn current_word_end(document: &Document, from: Position) -> Position {
+    let start = class(document, from);
+    let mut position = from;
+    while let Some(next) = next(document, position) {
+        if next.y != position.y || class(document, next) != start {
+            break;
+        }
+        position = next;
+    }
+    position
+}
+
+//searches the row for the count-th occurrence of the character
+fn find(document: &Document, from: Position, c: char, forward: bool, till: bool, count: usize) -> Option<Position> {
+    let row = document.row(from.y)?;
+    let target = c.to_string();
+    let matches = |x: &usize| row.slice(*x, x.saturating_add(1)) == target;
+    let x = if forward {
+        (from.x.saturating_add(1)..row.len()).filter(matches).nth(count.saturating_sub(1))?
+    } else {
+        (0..from.x).rev().filter(matches).nth(count.saturating_sub(1))?
+    };
+    let x = match (till, forward) {
+        (false, _) => x,
+        (true, true) => x.saturating_sub(1),
+        (true, false) => x.saturating_add(1),
+    };
+    Some(Position {x, y: from.y})
+}
+
+//where the motion takes the cursor, None if it can't move
+pub fn target(document: &Document, from: Position, motion: Motion, count: Option<usize>) -> Option<Position> {
+    let repeat = count.unwrap_or(1);
+    let last_line = document.len().saturating_sub(1);
+    let on_line = |y: usize| Position {x: first_non_blank(document, y), y};
+    let position = match motion {
+        Motion::Left => Position {x: from.x.saturating_sub(repeat), y: from.y},
+        Motion::Right => Position {
+            x: cmp::min(from.x.saturating_add(repeat), row_len(document, from.y)),
+            y: from.y,
+        },
+        Motion::Up | Motion::Down | Motion::Line => {
+            let y = match motion {
+                Motion::Up => from.y.saturating_sub(repeat),
+                Motion::Down => from.y.saturating_add(repeat),
+                _ => from.y.saturating_add(repeat.saturating_sub(1)),
+            };
+            let y = cmp::min(y, last_line);
+            Position {x: cmp::min(from.x, row_len(document, y)), y}
+        }
+        Motion::WordForward => (0..repeat).fold(from, |position, _| word_forward(document, position)),
+        Motion::WordBackward => (0..repeat).fold(from, |position, _| word_backward(document, position)),
+        Motion::WordEnd => (0..repeat).fold(from, |position, _| word_end(document, position)),
+        Motion::LineStart => Position {x: 0, y: from.y},
+        Motion::FirstNonBlank => on_line(from.y),
+        Motion::LineEnd => {
+            let y = cmp::min(from.y.saturating_add(repeat.saturating_sub(1)), last_line);
+            Position {x: row_len(document, y), y}
+        }
+        Motion::FirstLine => on_line(cmp::min(count.map_or(0, |line| line.saturating_sub(1)), last_line)),
+        Motion::LastLine => on_line(cmp::min(count.map_or(last_line, |line| line.saturating_sub(1)), last_line)),
+        Motion::Find {c, forward, till} => find(document, from, c, forward, till, repeat)?,
+    };
+    Some(position)
+}
+
+//what `cw` changes on a word: up to the end of the word like `ce`, except that
+//on the last character of a word that word is the first one, not the next
+pub fn change_word_range(document: &Document, from: Position, count: Option<usize>) -> Range {
+    let mut end = current_word_end(document, from);
+    for _ in 1..count.unwrap_or(1) {
+        end = word_end(document, end);
+    }
+    Range::Chars(from, next(document, end).unwrap_or(end))
+}
+
+//the text an operator combined with the motion works on
+pub fn range(document: &Document, from: Position, motion: Motion, count: Option<usize>) -> Option<Range> {
+    let to = target(document, from, motion, count)?;
+    let (start, end) = if to < from { (to, from) } else { (from, to) };
+    let linewise = matches!(
+        motion,
+        Motion::Up | Motion::Down | Motion::Line | Motion::FirstLine | Motion::LastLine
+    );
+    //these include the character they end on
+    let inclusive = matches!(motion, Motion::WordEnd | Motion::Find { forward: true, .. });
+    let range = if linewise {
+        Range::Lines(start.y, end.y)
+    } else if inclusive {
+        Range::Chars(start, next(document, end).unwrap_or(end))
+    } else if end.x == 0 && end.y > start.y {
+        //a motion that ends at the start of a later line stops at the end
+        //of the line before, so `dw` on the last word keeps the line break
+        let y = end.y.saturating_sub(1);
+        Range::Chars(start, Position {x: row_len(document, y), y})
+    } else {
+        Range::Chars(start, end)
+    };
+    Some(range)
+}